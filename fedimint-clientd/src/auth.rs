@@ -0,0 +1,40 @@
+use std::sync::Arc;
+
+use axum::body::Body;
+use axum::http::{header, HeaderValue, Request, Response, StatusCode};
+use tower_http::validate_request::ValidateRequest;
+
+/// Accepts requests carrying any of the configured bearer tokens.
+#[derive(Debug, Clone)]
+pub struct BearerAuth {
+    header_values: Arc<Vec<HeaderValue>>,
+}
+
+impl BearerAuth {
+    pub fn new(api_keys: &[String]) -> anyhow::Result<Self> {
+        let header_values = api_keys
+            .iter()
+            .map(|key| HeaderValue::from_str(&format!("Bearer {key}")))
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|e| anyhow::anyhow!("Invalid api key: {e}"))?;
+
+        Ok(Self {
+            header_values: Arc::new(header_values),
+        })
+    }
+}
+
+impl<B> ValidateRequest<B> for BearerAuth {
+    type ResponseBody = Body;
+
+    fn validate(&mut self, request: &mut Request<B>) -> Result<(), Response<Self::ResponseBody>> {
+        match request.headers().get(header::AUTHORIZATION) {
+            Some(actual) if self.header_values.iter().any(|expected| expected == actual) => Ok(()),
+            _ => {
+                let mut res = Response::new(Body::empty());
+                *res.status_mut() = StatusCode::UNAUTHORIZED;
+                Err(res)
+            }
+        }
+    }
+}
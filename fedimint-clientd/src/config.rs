@@ -0,0 +1,629 @@
+//! Configuration for fedimint-clientd.
+//!
+//! Values are resolved in order of precedence: command line flags, then
+//! environment variables, then the TOML file passed with `--config`, then
+//! defaults.
+//!
+//! Example config file:
+//!
+//! ```toml
+//! db_path = "/var/lib/fedimint-clientd"
+//! mode = "rest"
+//! invite_codes = ["fed11..."]
+//! default_federation = "15db8cb4f1ec8e484d73b889372bec94812580f929e8148b7437d359af422cd3"
+//! shutdown_timeout = 60
+//!
+//! [listen]
+//! addr = "127.0.0.1:3333"
+//! tls_cert_path = "/etc/fedimint-clientd/cert.pem"
+//! tls_key_path = "/etc/fedimint-clientd/key.pem"
+//!
+//! [auth]
+//! password = "some-secure-password"
+//! api_keys = ["another-bearer-token"]
+//!
+//! [gateway]
+//! default = "035f2f7912e0f570841d5c0d8976a40af0dcca5609198436f596e78d2c851ee58a"
+//! allowed = ["035f2f7912e0f570841d5c0d8976a40af0dcca5609198436f596e78d2c851ee58a"]
+//! refresh_interval = 300
+//!
+//! [limits]
+//! max_send_msat = 100000000
+//! max_invoice_msat = 100000000
+//!
+//! [metrics]
+//! addr = "127.0.0.1:3001"
+//! update_interval = 30
+//!
+//! [meta]
+//! refresh_interval = 600
+//!
+//! [logging]
+//! filter = "info,fedimint_client=warn"
+//! format = "json"
+//!
+//! [[tenants]]
+//! name = "acme"
+//! api_keys = ["acme-bearer-token"]
+//! invite_codes = ["fed11..."]
+//! ```
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+
+use anyhow::{anyhow, Context, Result};
+use axum::http::StatusCode;
+use clap::{Parser, Subcommand, ValueEnum};
+use multimint::fedimint_core::config::FederationId;
+use multimint::fedimint_core::invite_code::InviteCode;
+use multimint::fedimint_core::secp256k1::PublicKey;
+use multimint::fedimint_core::Amount;
+use serde::Deserialize;
+
+use crate::error::AppError;
+use crate::listener::{Listener, TlsConfig};
+use crate::router::handlers::admin::export_transactions::ExportFormat;
+
+const DEFAULT_PROMETHEUS_ADDR: &str = "127.0.0.1:3001";
+const DEFAULT_UNIX_SOCKET_MODE: &str = "660";
+const DEFAULT_SHUTDOWN_TIMEOUT_SECS: u64 = 60;
+const DEFAULT_METRICS_UPDATE_INTERVAL_SECS: u64 = 30;
+const DEFAULT_META_REFRESH_INTERVAL_SECS: u64 = 600;
+const DEFAULT_GATEWAY_REFRESH_INTERVAL_SECS: u64 = 300;
+
+#[derive(Clone, Debug, ValueEnum, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Mode {
+    Rest,
+    Ws,
+}
+
+impl FromStr for Mode {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "rest" => Ok(Mode::Rest),
+            "ws" => Ok(Mode::Ws),
+            _ => Err(anyhow::anyhow!("Invalid mode")),
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum Commands {
+    /// Run the server (default)
+    Start,
+    /// Validate the configuration and exit
+    CheckConfig,
+    /// Export the transaction history of the stored federations and exit
+    ExportTransactions {
+        /// Output format, `csv` or `jsonl`
+        #[clap(long, default_value = "csv")]
+        format: ExportFormat,
+        /// Only export this federation, defaults to all federations
+        #[clap(long)]
+        federation_id: Option<FederationId>,
+        /// Only include transactions created at or after this unix timestamp
+        #[clap(long)]
+        start_time: Option<u64>,
+        /// Only include transactions created before this unix timestamp
+        #[clap(long)]
+        end_time: Option<u64>,
+        /// Write to this file instead of stdout
+        #[clap(long, short)]
+        output: Option<PathBuf>,
+    },
+    /// Encrypt the stored mnemonic with a new passphrase, or change the one
+    /// it's encrypted with, and exit. The current passphrase is taken from
+    /// `--passphrase` or `--passphrase-stdin`.
+    ChangePassphrase {
+        /// New passphrase, prompted for if not set
+        #[clap(long, env = "FEDIMINT_CLIENTD_NEW_PASSPHRASE")]
+        new_passphrase: Option<String>,
+        /// Remove the encryption and store the mnemonic in plaintext
+        #[clap(long, conflicts_with = "new_passphrase")]
+        remove: bool,
+    },
+}
+
+#[derive(Parser)]
+#[clap(version = "1.0", author = "Kody Low")]
+pub struct Cli {
+    #[clap(subcommand)]
+    pub command: Option<Commands>,
+
+    /// Path to a TOML config file
+    #[clap(long, env = "FEDIMINT_CLIENTD_CONFIG")]
+    pub config: Option<PathBuf>,
+
+    /// Federation invite codes to join on startup, comma separated in the
+    /// environment variable
+    #[clap(
+        long = "invite-code",
+        env = "FEDIMINT_CLIENTD_INVITE_CODE",
+        value_delimiter = ','
+    )]
+    pub invite_codes: Vec<String>,
+
+    /// Federation used by requests that don't specify a `federationId`
+    #[clap(long, env = "FEDIMINT_CLIENTD_DEFAULT_FEDERATION")]
+    pub default_federation: Option<FederationId>,
+
+    /// Path to FM database
+    #[clap(long, env = "FEDIMINT_CLIENTD_DB_PATH")]
+    pub db_path: Option<PathBuf>,
+
+    /// Password
+    #[clap(long, env = "FEDIMINT_CLIENTD_PASSWORD")]
+    pub password: Option<String>,
+
+    /// Addr
+    #[clap(long, env = "FEDIMINT_CLIENTD_ADDR")]
+    pub addr: Option<String>,
+
+    /// Path to the PEM encoded TLS certificate chain, enables TLS on `addr`
+    #[clap(long, env = "FEDIMINT_CLIENTD_TLS_CERT_PATH")]
+    pub tls_cert_path: Option<PathBuf>,
+
+    /// Path to the PEM encoded TLS private key
+    #[clap(long, env = "FEDIMINT_CLIENTD_TLS_KEY_PATH")]
+    pub tls_key_path: Option<PathBuf>,
+
+    /// Path to a PEM encoded CA bundle, requires clients to present a
+    /// certificate signed by it (mTLS)
+    #[clap(long, env = "FEDIMINT_CLIENTD_TLS_CLIENT_CA_PATH")]
+    pub tls_client_ca_path: Option<PathBuf>,
+
+    /// Listen on a unix domain socket at this path instead of `addr`
+    #[clap(long, env = "FEDIMINT_CLIENTD_UNIX_SOCKET_PATH")]
+    pub unix_socket_path: Option<PathBuf>,
+
+    /// File mode of the unix domain socket, in octal [default: 660]
+    #[clap(long, env = "FEDIMINT_CLIENTD_UNIX_SOCKET_MODE")]
+    pub unix_socket_mode: Option<String>,
+
+    /// Prometheus addr [default: 127.0.0.1:3001]
+    #[clap(long, env = "PROMETHEUS_ADDR")]
+    pub prometheus_addr: Option<String>,
+
+    /// Passphrase the stored mnemonic is encrypted with. A plaintext mnemonic
+    /// is encrypted with it.
+    #[clap(long, env = "FEDIMINT_CLIENTD_PASSPHRASE")]
+    pub passphrase: Option<String>,
+
+    /// Prompt for the mnemonic's passphrase on the terminal
+    #[clap(long, conflicts_with = "passphrase")]
+    pub passphrase_stdin: bool,
+
+    /// Mode: ws, rest [default: rest]
+    #[clap(long, env = "FEDIMINT_CLIENTD_MODE")]
+    pub mode: Option<Mode>,
+
+    /// Seconds to wait for in-flight requests to complete on shutdown
+    /// [default: 60]
+    #[clap(long, env = "FEDIMINT_CLIENTD_SHUTDOWN_TIMEOUT")]
+    pub shutdown_timeout: Option<u64>,
+
+    /// Seconds between refreshes of the cached federation meta [default: 600]
+    #[clap(long, env = "FEDIMINT_CLIENTD_META_REFRESH_INTERVAL")]
+    pub meta_refresh_interval: Option<u64>,
+}
+
+/// Contents of the `--config` file, every value is optional and can be
+/// overridden by the corresponding flag or environment variable.
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FileConfig {
+    pub db_path: Option<PathBuf>,
+    pub mode: Option<Mode>,
+    #[serde(default)]
+    pub invite_codes: Vec<InviteCode>,
+    pub default_federation: Option<FederationId>,
+    pub shutdown_timeout: Option<u64>,
+    #[serde(default)]
+    pub listen: ListenConfig,
+    #[serde(default)]
+    pub auth: AuthConfig,
+    #[serde(default)]
+    pub gateway: GatewayPolicy,
+    #[serde(default)]
+    pub limits: Limits,
+    #[serde(default)]
+    pub metrics: MetricsConfig,
+    #[serde(default)]
+    pub meta: MetaConfig,
+    #[serde(default)]
+    pub logging: LoggingConfig,
+    #[serde(default)]
+    pub tenants: Vec<TenantConfig>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ListenConfig {
+    pub addr: Option<String>,
+    pub tls_cert_path: Option<PathBuf>,
+    pub tls_key_path: Option<PathBuf>,
+    pub tls_client_ca_path: Option<PathBuf>,
+    pub unix_socket_path: Option<PathBuf>,
+    pub unix_socket_mode: Option<String>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AuthConfig {
+    pub password: Option<String>,
+    /// Additional bearer tokens accepted alongside the password
+    #[serde(default)]
+    pub api_keys: Vec<String>,
+}
+
+/// Which gateways lightning requests may use.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct GatewayPolicy {
+    /// Gateway used when a request doesn't specify one
+    pub default: Option<PublicKey>,
+    /// If not empty, only these gateways may be used
+    #[serde(default)]
+    pub allowed: Vec<PublicKey>,
+    /// Seconds between refreshes of the clients' gateway caches
+    pub refresh_interval: Option<u64>,
+}
+
+impl GatewayPolicy {
+    /// Resolves the gateway to use for a request, falling back to the
+    /// configured default.
+    pub fn resolve(&self, gateway_id: Option<PublicKey>) -> Result<PublicKey, AppError> {
+        let gateway_id = gateway_id.or(self.default).ok_or_else(|| {
+            AppError::new(
+                StatusCode::BAD_REQUEST,
+                anyhow!("No gateway id provided and no default gateway configured"),
+            )
+        })?;
+
+        if !self.allowed.is_empty() && !self.allowed.contains(&gateway_id) {
+            return Err(AppError::new(
+                StatusCode::FORBIDDEN,
+                anyhow!("Gateway {gateway_id} is not allowed by the gateway policy"),
+            ));
+        }
+
+        Ok(gateway_id)
+    }
+
+    /// LNv2 gateways are identified by their url, not a node pubkey, so
+    /// they can't be checked against `allowed` or selected by `gateway_id`.
+    pub fn allows_lnv2(&self, gateway_id: Option<PublicKey>) -> bool {
+        self.allowed.is_empty() && gateway_id.is_none()
+    }
+}
+
+/// Per request amount limits, unset limits are not enforced.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Limits {
+    /// Max amount for lightning payments, ecash spends and onchain withdrawals
+    pub max_send_msat: Option<Amount>,
+    /// Max amount for created invoices
+    pub max_invoice_msat: Option<Amount>,
+}
+
+impl Limits {
+    pub fn check_send(&self, amount: Amount) -> Result<(), AppError> {
+        check_limit(amount, self.max_send_msat, "send")
+    }
+
+    pub fn check_invoice(&self, amount: Amount) -> Result<(), AppError> {
+        check_limit(amount, self.max_invoice_msat, "invoice")
+    }
+}
+
+fn check_limit(amount: Amount, max: Option<Amount>, kind: &str) -> Result<(), AppError> {
+    match max {
+        Some(max) if amount > max => Err(AppError::new(
+            StatusCode::BAD_REQUEST,
+            anyhow!("Amount {amount} exceeds the {kind} limit of {max}"),
+        )),
+        _ => Ok(()),
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MetricsConfig {
+    pub addr: Option<String>,
+    /// Seconds between updates of the balance and operation metrics
+    pub update_interval: Option<u64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct MetaConfig {
+    /// Seconds between refreshes of the cached federation meta
+    pub refresh_interval: Option<u64>,
+}
+
+/// An independent wallet served alongside the default one, see `tenant`.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TenantConfig {
+    /// Used in the tenant's path prefix and data directory
+    pub name: String,
+    /// Bearer tokens giving access to this tenant only
+    pub api_keys: Vec<String>,
+    /// Federation invite codes to join on startup
+    #[serde(default)]
+    pub invite_codes: Vec<InviteCode>,
+    pub default_federation: Option<FederationId>,
+}
+
+impl TenantConfig {
+    /// The tenant's multimint directory inside the main `db_path`
+    pub fn db_path(&self, db_path: &Path) -> PathBuf {
+        db_path.join("tenants").join(&self.name)
+    }
+}
+
+/// Checks that tenant names are usable as path segments and that no api key
+/// gives access to more than one tenant.
+fn check_tenants(tenants: &[TenantConfig], api_keys: &[String]) -> Result<()> {
+    let mut names = BTreeSet::new();
+    let mut keys = api_keys.iter().collect::<BTreeSet<_>>();
+    for tenant in tenants {
+        if tenant.name.is_empty()
+            || !tenant
+                .name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+        {
+            return Err(anyhow!(
+                "Invalid tenant name {:?}, only letters, digits, `-` and `_` are allowed",
+                tenant.name
+            ));
+        }
+        if !names.insert(&tenant.name) {
+            return Err(anyhow!("Duplicate tenant name {}", tenant.name));
+        }
+        if tenant.api_keys.is_empty() {
+            return Err(anyhow!("Tenant {} has no api keys", tenant.name));
+        }
+        for key in &tenant.api_keys {
+            if !keys.insert(key) {
+                return Err(anyhow!(
+                    "An api key of tenant {} is already used by another tenant or the default wallet",
+                    tenant.name
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LoggingConfig {
+    /// `tracing` filter directives, overridden by `RUST_LOG`
+    pub filter: Option<String>,
+    #[serde(default)]
+    pub format: LogFormat,
+}
+
+#[derive(Debug, Clone, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    #[default]
+    Text,
+    Json,
+}
+
+/// The fully resolved configuration the server runs with.
+#[derive(Debug)]
+pub struct Config {
+    pub db_path: PathBuf,
+    pub mode: Mode,
+    pub listener: Listener,
+    pub api_keys: Vec<String>,
+    pub invite_codes: Vec<InviteCode>,
+    pub default_federation: Option<FederationId>,
+    pub gateway_policy: GatewayPolicy,
+    pub limits: Limits,
+    pub prometheus_addr: String,
+    pub metrics_update_interval: u64,
+    pub meta_refresh_interval: u64,
+    pub gateway_refresh_interval: u64,
+    pub logging: LoggingConfig,
+    pub shutdown_timeout: u64,
+    pub tenants: Vec<TenantConfig>,
+}
+
+impl FileConfig {
+    pub fn load(path: &PathBuf) -> Result<Self> {
+        let contents = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read config file {}", path.display()))?;
+        toml::from_str(&contents).with_context(|| format!("Invalid config file {}", path.display()))
+    }
+}
+
+/// Resolves only the database path, for offline subcommands that don't need
+/// a listener or credentials.
+pub fn db_path_from_cli(cli: &Cli) -> Result<PathBuf> {
+    let file = match &cli.config {
+        Some(path) => FileConfig::load(path)?,
+        None => FileConfig::default(),
+    };
+    cli.db_path
+        .clone()
+        .or(file.db_path)
+        .ok_or_else(|| anyhow!("`db_path` is required (--db-path)"))
+}
+
+/// Resolves the passphrase of the stored mnemonic, prompting for it with
+/// `--passphrase-stdin`.
+pub fn passphrase_from_cli(cli: &Cli) -> Result<Option<String>> {
+    if cli.passphrase_stdin {
+        return Ok(Some(prompt_passphrase("Passphrase: ")?));
+    }
+    Ok(cli.passphrase.clone())
+}
+
+pub fn prompt_passphrase(prompt: &str) -> Result<String> {
+    rpassword::prompt_password(prompt).context("Failed to read the passphrase")
+}
+
+impl Config {
+    /// Merges the command line and environment with the config file, if any,
+    /// and validates the result.
+    pub fn from_cli(cli: &Cli) -> Result<Self> {
+        let file = match &cli.config {
+            Some(path) => FileConfig::load(path)?,
+            None => FileConfig::default(),
+        };
+
+        let db_path = cli
+            .db_path
+            .clone()
+            .or(file.db_path)
+            .ok_or_else(|| anyhow!("`db_path` is required (--db-path)"))?;
+
+        let mut api_keys = Vec::new();
+        if let Some(password) = cli.password.clone().or(file.auth.password) {
+            api_keys.push(password);
+        }
+        api_keys.extend(file.auth.api_keys);
+        if api_keys.is_empty() {
+            return Err(anyhow!(
+                "A password or at least one api key is required (--password)"
+            ));
+        }
+        check_tenants(&file.tenants, &api_keys)?;
+
+        let invite_codes = if cli.invite_codes.is_empty() {
+            file.invite_codes
+        } else {
+            cli.invite_codes
+                .iter()
+                .map(|invite_code| {
+                    InviteCode::from_str(invite_code.trim())
+                        .map_err(|e| anyhow!("Invalid invite code {invite_code}: {e}"))
+                })
+                .collect::<Result<Vec<_>>>()?
+        };
+
+        let listener = listener_from_parts(
+            cli.addr.clone().or(file.listen.addr),
+            cli.tls_cert_path.clone().or(file.listen.tls_cert_path),
+            cli.tls_key_path.clone().or(file.listen.tls_key_path),
+            cli.tls_client_ca_path
+                .clone()
+                .or(file.listen.tls_client_ca_path),
+            cli.unix_socket_path
+                .clone()
+                .or(file.listen.unix_socket_path),
+            cli.unix_socket_mode
+                .clone()
+                .or(file.listen.unix_socket_mode)
+                .unwrap_or_else(|| DEFAULT_UNIX_SOCKET_MODE.to_string()),
+        )?;
+
+        Ok(Self {
+            db_path,
+            mode: cli.mode.clone().or(file.mode).unwrap_or(Mode::Rest),
+            listener,
+            api_keys,
+            invite_codes,
+            default_federation: cli.default_federation.or(file.default_federation),
+            gateway_refresh_interval: file
+                .gateway
+                .refresh_interval
+                .unwrap_or(DEFAULT_GATEWAY_REFRESH_INTERVAL_SECS),
+            gateway_policy: file.gateway,
+            limits: file.limits,
+            prometheus_addr: cli
+                .prometheus_addr
+                .clone()
+                .or(file.metrics.addr)
+                .unwrap_or_else(|| DEFAULT_PROMETHEUS_ADDR.to_string()),
+            metrics_update_interval: file
+                .metrics
+                .update_interval
+                .unwrap_or(DEFAULT_METRICS_UPDATE_INTERVAL_SECS),
+            meta_refresh_interval: cli
+                .meta_refresh_interval
+                .or(file.meta.refresh_interval)
+                .unwrap_or(DEFAULT_META_REFRESH_INTERVAL_SECS),
+            logging: file.logging,
+            shutdown_timeout: cli
+                .shutdown_timeout
+                .or(file.shutdown_timeout)
+                .unwrap_or(DEFAULT_SHUTDOWN_TIMEOUT_SECS),
+            tenants: file.tenants,
+        })
+    }
+
+    /// Performs the checks that need to touch the filesystem, like loading the
+    /// TLS certificates, without starting anything.
+    pub fn check(&self) -> Result<()> {
+        self.listener.check()?;
+        if let Some(default) = self.gateway_policy.default {
+            if !self.gateway_policy.allowed.is_empty()
+                && !self.gateway_policy.allowed.contains(&default)
+            {
+                return Err(anyhow!(
+                    "Default gateway {default} is not in the allowed gateways"
+                ));
+            }
+        }
+        if let Some(filter) = &self.logging.filter {
+            tracing_subscriber::EnvFilter::try_new(filter)
+                .map_err(|e| anyhow!("Invalid logging filter {filter}: {e}"))?;
+        }
+        Ok(())
+    }
+}
+
+fn listener_from_parts(
+    addr: Option<String>,
+    tls_cert_path: Option<PathBuf>,
+    tls_key_path: Option<PathBuf>,
+    tls_client_ca_path: Option<PathBuf>,
+    unix_socket_path: Option<PathBuf>,
+    unix_socket_mode: String,
+) -> Result<Listener> {
+    if let Some(path) = unix_socket_path {
+        if addr.is_some() || tls_cert_path.is_some() {
+            return Err(anyhow!(
+                "`unix_socket_path` can't be combined with `addr` or TLS"
+            ));
+        }
+        let mode = u32::from_str_radix(&unix_socket_mode, 8)
+            .map_err(|e| anyhow!("Invalid unix socket mode: {e}"))?;
+        #[cfg(unix)]
+        return Ok(Listener::Unix { path, mode });
+        #[cfg(not(unix))]
+        return Err(anyhow!(
+            "Unix domain sockets are not supported on this platform: {} ({mode:o})",
+            path.display()
+        ));
+    }
+
+    let addr =
+        addr.ok_or_else(|| anyhow!("Either `addr` or `unix_socket_path` is required (--addr)"))?;
+
+    match (tls_cert_path, tls_key_path) {
+        (Some(cert_path), Some(key_path)) => Ok(Listener::Tls {
+            addr,
+            tls: TlsConfig {
+                cert_path,
+                key_path,
+                client_ca_path: tls_client_ca_path,
+            },
+        }),
+        (None, None) if tls_client_ca_path.is_none() => Ok(Listener::Tcp { addr }),
+        _ => Err(anyhow!(
+            "TLS requires both `tls_cert_path` and `tls_key_path`"
+        )),
+    }
+}
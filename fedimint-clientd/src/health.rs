@@ -0,0 +1,319 @@
+//! Liveness and readiness probes.
+//!
+//! `/health/live` only reports that the process is serving requests.
+//! `/health/ready` returns `503 Service Unavailable` unless the database
+//! accepts writes and a threshold of the default federation's guardians
+//! answer, with the details of each check in the body. Other federations that
+//! are unreachable or still loading only mark the wallet as degraded. The
+//! checks cover the default wallet and every tenant.
+//!
+//! Asking every guardian takes up to `PEER_TIMEOUT`, so the federations are
+//! checked in the background every `FEDERATION_CHECK_INTERVAL` and the probe
+//! reads the latest results.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use multimint::fedimint_client::db::{CachedApiVersionSet, CachedApiVersionSetKey};
+use multimint::fedimint_client::ClientHandleArc;
+use multimint::fedimint_core::config::FederationId;
+use multimint::fedimint_core::db::IDatabaseTransactionOpsCoreTyped;
+use multimint::fedimint_ln_client::LightningClientModule;
+use multimint::types::{GatewayCacheStatus, LoadStatus};
+use serde::Serialize;
+use serde_json::{json, Value};
+use tokio::sync::RwLock;
+use tracing::debug;
+
+use crate::router::handlers::admin::federation_status::federation_status;
+use crate::state::AppState;
+
+/// How often the guardians of every federation are asked whether they are
+/// reachable
+const FEDERATION_CHECK_INTERVAL: Duration = Duration::from_secs(30);
+
+/// Latest health of the federations of the default wallet (`None`) and of
+/// each tenant
+type FederationHealthCache = BTreeMap<Option<String>, BTreeMap<FederationId, FederationHealth>>;
+
+/// State of the readiness probe, the default wallet and the tenants.
+#[derive(Debug, Clone)]
+pub struct HealthState {
+    pub state: AppState,
+    pub tenants: Vec<(String, AppState)>,
+    federations: Arc<RwLock<FederationHealthCache>>,
+}
+
+impl HealthState {
+    pub fn new(state: AppState, tenants: Vec<(String, AppState)>) -> Self {
+        Self {
+            state,
+            tenants,
+            federations: Arc::default(),
+        }
+    }
+
+    /// Spawns the task checking the federations of the default wallet and of
+    /// the tenants every `FEDERATION_CHECK_INTERVAL`.
+    pub fn spawn_federation_checks(&self) {
+        let health_state = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(FEDERATION_CHECK_INTERVAL);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            loop {
+                ticker.tick().await;
+                debug!("Checking the federations");
+                let wallets = std::iter::once((None, &health_state.state)).chain(
+                    health_state
+                        .tenants
+                        .iter()
+                        .map(|(name, state)| (Some(name.clone()), state)),
+                );
+                for (tenant, state) in wallets {
+                    let federations = federations_health(state).await;
+                    health_state
+                        .federations
+                        .write()
+                        .await
+                        .insert(tenant, federations);
+                }
+            }
+        });
+    }
+
+    async fn wallet_health(&self, tenant: Option<&str>, state: &AppState) -> WalletHealth {
+        let database = CheckResult::from_result(state.multimint.check_db_writeable().await);
+        let federations = self
+            .federations
+            .read()
+            .await
+            .get(&tenant.map(str::to_owned))
+            .cloned()
+            .unwrap_or_default();
+        let loading = state.multimint.loading().await;
+        let default_federation = state.multimint.default_federation().await;
+
+        let degraded = !loading.is_empty()
+            || federations.iter().any(|(federation_id, federation)| {
+                Some(*federation_id) != default_federation && !federation.connected
+            });
+
+        WalletHealth {
+            database,
+            default_federation,
+            degraded,
+            federations,
+            loading,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReadinessResponse {
+    /// Whether the default wallet and every tenant are ready
+    pub ready: bool,
+    #[serde(flatten)]
+    pub wallet: WalletHealth,
+    #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+    pub tenants: BTreeMap<String, TenantHealth>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TenantHealth {
+    pub ready: bool,
+    #[serde(flatten)]
+    pub wallet: WalletHealth,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct WalletHealth {
+    pub database: CheckResult,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub default_federation: Option<FederationId>,
+    /// Another federation is unreachable or still loading, which doesn't
+    /// affect readiness
+    pub degraded: bool,
+    /// Result of the latest background check of each federation
+    pub federations: BTreeMap<FederationId, FederationHealth>,
+    /// Clients that are still being opened, with the last error if opening
+    /// them failed
+    pub loading: BTreeMap<FederationId, LoadStatus>,
+}
+
+impl WalletHealth {
+    /// Ready once the database accepts writes and the default federation is
+    /// reachable.
+    fn ready(&self) -> bool {
+        self.database.ok
+            && self
+                .default_federation
+                .and_then(|federation_id| self.federations.get(&federation_id))
+                .is_some_and(|federation| federation.connected)
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CheckResult {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl CheckResult {
+    fn from_result<T>(result: anyhow::Result<T>) -> Self {
+        match result {
+            Ok(_) => CheckResult {
+                ok: true,
+                error: None,
+            },
+            Err(e) => CheckResult {
+                ok: false,
+                error: Some(e.to_string()),
+            },
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FederationHealth {
+    /// Whether at least `threshold` guardians answered
+    pub connected: bool,
+    pub online_guardians: usize,
+    /// Number of guardians needed to reach consensus
+    pub threshold: usize,
+    /// The client is recovering its funds, its modules are unavailable
+    pub recovering: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_version: Option<Value>,
+    /// Number of gateways in the client's gateway cache, lightning payments
+    /// fail without one but ecash and onchain keep working
+    pub gateways: usize,
+    /// Refreshes of the gateway cache, absent if it was never refreshed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub gateway_cache: Option<GatewayCacheStatus>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+async fn federation_health(state: &AppState, client: &ClientHandleArc) -> FederationHealth {
+    let recovering = state.multimint.is_recovering(&client.federation_id()).await;
+    let has_lightning = client
+        .get_first_instance(&multimint::fedimint_ln_common::KIND)
+        .is_some();
+    let gateways = if recovering || !has_lightning {
+        0
+    } else {
+        client
+            .get_first_module::<LightningClientModule>()
+            .list_gateways()
+            .await
+            .len()
+    };
+
+    let gateway_cache = state
+        .multimint
+        .gateway_cache_status()
+        .await
+        .remove(&client.federation_id());
+
+    // Every guardian is asked for its session count, each request times out
+    // on its own
+    let status = federation_status(client).await;
+    let connected = !status.offline;
+    let error = (!connected).then(|| {
+        format!(
+            "Only {} of {} guardians answered, {} are needed",
+            status.online_guardians, status.total_guardians, status.threshold
+        )
+    });
+
+    // The common api version negotiated with the guardians, as cached by the
+    // client
+    let api_version = client
+        .db()
+        .begin_transaction_nc()
+        .await
+        .get_value(&CachedApiVersionSetKey)
+        .await
+        .and_then(|CachedApiVersionSet(api_version)| serde_json::to_value(api_version).ok());
+
+    FederationHealth {
+        connected,
+        online_guardians: status.online_guardians,
+        threshold: status.threshold,
+        recovering,
+        api_version,
+        gateways,
+        gateway_cache,
+        error,
+    }
+}
+
+pub async fn live() -> Json<Value> {
+    Json(json!({ "status": "ok" }))
+}
+
+async fn federations_health(state: &AppState) -> BTreeMap<FederationId, FederationHealth> {
+    let clients = state.multimint.all().await;
+    let health = futures::future::join_all(
+        clients
+            .iter()
+            .map(|client| federation_health(state, client)),
+    )
+    .await;
+    clients
+        .iter()
+        .map(|client| client.federation_id())
+        .zip(health)
+        .collect()
+}
+
+pub async fn ready(
+    State(health_state): State<HealthState>,
+) -> (StatusCode, Json<ReadinessResponse>) {
+    let (wallet, tenants) = futures::future::join(
+        health_state.wallet_health(None, &health_state.state),
+        futures::future::join_all(
+            health_state
+                .tenants
+                .iter()
+                .map(|(name, state)| health_state.wallet_health(Some(name), state)),
+        ),
+    )
+    .await;
+    let tenants = health_state
+        .tenants
+        .iter()
+        .map(|(name, _)| name.clone())
+        .zip(tenants)
+        .map(|(name, wallet)| {
+            let ready = wallet.ready();
+            (name, TenantHealth { ready, wallet })
+        })
+        .collect::<BTreeMap<_, _>>();
+
+    let ready = wallet.ready() && tenants.values().all(|tenant| tenant.ready);
+    let status = if ready {
+        StatusCode::OK
+    } else {
+        StatusCode::SERVICE_UNAVAILABLE
+    };
+
+    (
+        status,
+        Json(ReadinessResponse {
+            ready,
+            wallet,
+            tenants,
+        }),
+    )
+}
@@ -0,0 +1,262 @@
+//! Listeners the main server can be bound to: plain TCP, TCP with TLS
+//! termination (optionally requiring client certificates), or a Unix domain
+//! socket protected by its file permissions.
+
+use std::fmt;
+use std::fs::File;
+use std::io::BufReader;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use anyhow::{anyhow, Context, Result};
+use axum::Router;
+use axum_server::tls_rustls::RustlsConfig;
+use tracing::info;
+
+/// Paths to the PEM encoded files used for TLS termination.
+#[derive(Debug, Clone)]
+pub struct TlsConfig {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
+    /// CA bundle used to verify client certificates. When set, clients must
+    /// present a certificate signed by one of these CAs (mTLS).
+    pub client_ca_path: Option<PathBuf>,
+}
+
+#[derive(Debug, Clone)]
+pub enum Listener {
+    Tcp {
+        addr: String,
+    },
+    Tls {
+        addr: String,
+        tls: TlsConfig,
+    },
+    #[cfg(unix)]
+    Unix {
+        path: PathBuf,
+        /// File mode applied to the socket, e.g. `0o660`
+        mode: u32,
+    },
+}
+
+impl fmt::Display for Listener {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Listener::Tcp { addr } => write!(f, "http://{addr}"),
+            Listener::Tls { addr, tls } => match tls.client_ca_path {
+                Some(_) => write!(f, "https://{addr} (mTLS)"),
+                None => write!(f, "https://{addr}"),
+            },
+            #[cfg(unix)]
+            Listener::Unix { path, mode } => write!(f, "unix:{} ({mode:o})", path.display()),
+        }
+    }
+}
+
+impl Listener {
+    /// Validates the listener's configuration without binding it.
+    pub fn check(&self) -> Result<()> {
+        match self {
+            Listener::Tcp { addr } | Listener::Tls { addr, .. } => {
+                addr.parse::<SocketAddr>()
+                    .with_context(|| format!("Invalid socket address: {addr}"))?;
+            }
+            #[cfg(unix)]
+            Listener::Unix { .. } => {}
+        }
+        if let Listener::Tls { tls, .. } = self {
+            tls.server_config()?;
+        }
+        Ok(())
+    }
+
+    /// Binds the listener and serves `app` on it until the server fails.
+    pub async fn serve(self, app: Router) -> Result<()> {
+        info!("fedimint-clientd listening on {self}");
+        match self {
+            Listener::Tcp { addr } => {
+                let listener = tokio::net::TcpListener::bind(&addr).await?;
+                axum::serve(listener, app).await?;
+            }
+            Listener::Tls { addr, tls } => {
+                let addr = addr
+                    .parse::<SocketAddr>()
+                    .with_context(|| format!("Invalid socket address: {addr}"))?;
+                let config = RustlsConfig::from_config(Arc::new(tls.server_config()?));
+                axum_server::bind_rustls(addr, config)
+                    .serve(app.into_make_service())
+                    .await?;
+            }
+            #[cfg(unix)]
+            Listener::Unix { path, mode } => unix::serve(&path, mode, app).await?,
+        }
+        Ok(())
+    }
+}
+
+impl TlsConfig {
+    fn server_config(&self) -> Result<rustls::ServerConfig> {
+        let certs = load_certs(&self.cert_path)?;
+        let key = load_private_key(&self.key_path)?;
+
+        let builder = rustls::ServerConfig::builder().with_safe_defaults();
+        let builder = match &self.client_ca_path {
+            Some(client_ca_path) => {
+                let mut roots = rustls::RootCertStore::empty();
+                for cert in load_certs(client_ca_path)? {
+                    roots.add(&cert)?;
+                }
+                builder.with_client_cert_verifier(
+                    rustls::server::AllowAnyAuthenticatedClient::new(roots).boxed(),
+                )
+            }
+            None => builder.with_no_client_auth(),
+        };
+
+        let mut config = builder.with_single_cert(certs, key)?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(config)
+    }
+}
+
+fn load_certs(path: &Path) -> Result<Vec<rustls::Certificate>> {
+    let mut reader = BufReader::new(
+        File::open(path).with_context(|| format!("Failed to open {}", path.display()))?,
+    );
+    let certs = rustls_pemfile::certs(&mut reader)
+        .map(|cert| cert.map(|cert| rustls::Certificate(cert.to_vec())))
+        .collect::<Result<Vec<_>, _>>()?;
+    if certs.is_empty() {
+        return Err(anyhow!("No certificates found in {}", path.display()));
+    }
+    Ok(certs)
+}
+
+fn load_private_key(path: &Path) -> Result<rustls::PrivateKey> {
+    let mut reader = BufReader::new(
+        File::open(path).with_context(|| format!("Failed to open {}", path.display()))?,
+    );
+    let key = rustls_pemfile::private_key(&mut reader)?
+        .ok_or_else(|| anyhow!("No private key found in {}", path.display()))?;
+    Ok(rustls::PrivateKey(key.secret_der().to_vec()))
+}
+
+#[cfg(unix)]
+mod unix {
+    use std::os::unix::fs::{DirBuilderExt, FileTypeExt, PermissionsExt};
+    use std::path::Path;
+    use std::time::Duration;
+
+    use anyhow::{Context, Result};
+    use axum::Router;
+    use hyper_util::rt::{TokioExecutor, TokioIo};
+    use hyper_util::server::conn::auto::Builder;
+    use hyper_util::service::TowerToHyperService;
+    use tokio::net::UnixListener;
+    use tracing::{debug, warn};
+
+    /// How long to back off after failing to accept a connection, e.g. when
+    /// running out of file descriptors
+    const ACCEPT_ERROR_DELAY: Duration = Duration::from_millis(100);
+
+    pub async fn serve(path: &Path, mode: u32, app: Router) -> Result<()> {
+        remove_stale_socket(path)?;
+        let listener = bind(path, mode)?;
+
+        loop {
+            let socket = match listener.accept().await {
+                Ok((socket, _)) => socket,
+                Err(e) => {
+                    warn!("Failed to accept unix socket connection: {e}");
+                    tokio::time::sleep(ACCEPT_ERROR_DELAY).await;
+                    continue;
+                }
+            };
+            let service = TowerToHyperService::new(app.clone());
+            tokio::spawn(async move {
+                debug!("Accepted unix socket connection");
+                if let Err(e) = Builder::new(TokioExecutor::new())
+                    .serve_connection_with_upgrades(TokioIo::new(socket), service)
+                    .await
+                {
+                    warn!("Failed to serve unix socket connection: {e}");
+                }
+            });
+        }
+    }
+
+    /// Removes a socket left by a previous run, which would make the bind
+    /// fail. Anything else at `path` is left alone.
+    fn remove_stale_socket(path: &Path) -> Result<()> {
+        match std::fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.file_type().is_socket() => {
+                std::fs::remove_file(path)?;
+                Ok(())
+            }
+            Ok(_) => anyhow::bail!(
+                "Refusing to replace {}, it exists and is not a socket",
+                path.display()
+            ),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(e).with_context(|| format!("Failed to inspect {}", path.display())),
+        }
+    }
+
+    /// Binds the socket inside a directory only we can access and moves it
+    /// into place once its mode is set, so it's never reachable with the
+    /// permissions given by the umask.
+    fn bind(path: &Path, mode: u32) -> Result<UnixListener> {
+        let file_name = path
+            .file_name()
+            .with_context(|| format!("Invalid socket path: {}", path.display()))?;
+        let parent = path.parent().unwrap_or_else(|| Path::new("."));
+        let private_dir = parent.join(format!(
+            ".{}.{}",
+            file_name.to_string_lossy(),
+            std::process::id()
+        ));
+        std::fs::DirBuilder::new()
+            .mode(0o700)
+            .create(&private_dir)
+            .with_context(|| format!("Failed to create {}", private_dir.display()))?;
+
+        let bound = (|| -> Result<UnixListener> {
+            let tmp_path = private_dir.join(file_name);
+            let listener = UnixListener::bind(&tmp_path)?;
+            std::fs::set_permissions(&tmp_path, std::fs::Permissions::from_mode(mode))?;
+            // Something may have been created at `path` since it was checked
+            remove_stale_socket(path)?;
+            std::fs::rename(&tmp_path, path)?;
+            Ok(listener)
+        })();
+        let _ = std::fs::remove_dir_all(&private_dir);
+        bound
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn only_sockets_are_removed() -> Result<()> {
+            let dir = std::env::temp_dir().join(format!("clientd-listener-{}", std::process::id()));
+            std::fs::create_dir_all(&dir)?;
+
+            let file = dir.join("data");
+            std::fs::write(&file, "keep me")?;
+            assert!(remove_stale_socket(&file).is_err());
+            assert_eq!(std::fs::read_to_string(&file)?, "keep me");
+
+            let socket = dir.join("socket");
+            drop(std::os::unix::net::UnixListener::bind(&socket)?);
+            remove_stale_socket(&socket)?;
+            assert!(!socket.exists());
+            remove_stale_socket(&socket)?;
+
+            std::fs::remove_dir_all(&dir)?;
+            Ok(())
+        }
+    }
+}
@@ -1,144 +1,353 @@
 use std::future::ready;
-use std::path::PathBuf;
-use std::str::FromStr;
-use std::time::Instant;
+use std::time::{Duration, Instant};
 
 use anyhow::Result;
-use axum::extract::{MatchedPath, Request};
-use axum::http::Method;
+use axum::extract::{MatchedPath, Request, State};
+use axum::http::{Method, StatusCode};
 use axum::middleware::{self, Next};
 use axum::response::IntoResponse;
 use futures::future::TryFutureExt;
 use futures::try_join;
 use metrics_exporter_prometheus::{Matcher, PrometheusBuilder, PrometheusHandle};
-use multimint::fedimint_core::invite_code::InviteCode;
-use router::handlers::{admin, ln, mint, onchain};
+use router::handlers::{accounts, admin, ln, mint, onchain};
 use router::ws::websocket_handler;
 use tower_http::cors::{Any, CorsLayer};
 use tower_http::trace::TraceLayer;
-use tracing::info;
+use tracing::{error, info, warn};
 
+mod auth;
+mod config;
 mod error;
+mod health;
+mod listener;
+mod meta;
 mod router;
 mod state;
+mod tenant;
 mod utils;
+mod wallet_metrics;
 
+use auth::BearerAuth;
 use axum::routing::{get, post};
 use axum::Router;
-use clap::{Parser, Subcommand, ValueEnum};
-use state::AppState;
+use clap::Parser;
+use config::{Cli, Commands, Config, LogFormat, LoggingConfig, Mode};
+use listener::Listener;
+use multimint::fedimint_core::config::FederationId;
+use multimint::fedimint_core::invite_code::InviteCode;
+use multimint::MultiMint;
+use router::handlers::admin::export_transactions::{
+    export_transactions, ExportTransactionsRequest,
+};
+use state::{ActiveRequests, AppState};
+use tenant::TenantKeys;
 // use tower_http::cors::{Any, CorsLayer};
 use tower_http::validate_request::ValidateRequestHeaderLayer;
+use tracing_subscriber::EnvFilter;
 
-#[derive(Clone, Debug, ValueEnum, PartialEq)]
-enum Mode {
-    Rest,
-    Ws,
-}
+// const PID_FILE: &str = "/tmp/fedimint_http.pid";
 
-impl FromStr for Mode {
-    type Err = anyhow::Error;
+#[tokio::main]
+async fn main() -> Result<()> {
+    dotenv::dotenv().ok();
+
+    let cli: Cli = Cli::parse();
 
-    fn from_str(s: &str) -> Result<Self, Self::Err> {
-        match s {
-            "rest" => Ok(Mode::Rest),
-            "ws" => Ok(Mode::Ws),
-            _ => Err(anyhow::anyhow!("Invalid mode")),
+    if let Some(Commands::ExportTransactions {
+        format,
+        federation_id,
+        start_time,
+        end_time,
+        output,
+    }) = &cli.command
+    {
+        let mut builder = MultiMint::builder();
+        if let Some(passphrase) = config::passphrase_from_cli(&cli)? {
+            builder = builder.with_passphrase(passphrase);
         }
+        let multimint = builder.build(config::db_path_from_cli(&cli)?).await?;
+        let failed = multimint.wait_for_loading().await;
+        let clients = match federation_id {
+            Some(federation_id) => {
+                if let Some(status) = failed.get(federation_id) {
+                    multimint.shutdown().await;
+                    return Err(anyhow::anyhow!(
+                        "Failed to load the client for federation id {federation_id}: {}",
+                        status.error.as_deref().unwrap_or("unknown error")
+                    ));
+                }
+                vec![multimint.get(federation_id).await.ok_or_else(|| {
+                    anyhow::anyhow!("No client for federation id {federation_id}")
+                })?]
+            }
+            None => {
+                for (federation_id, status) in &failed {
+                    warn!(
+                        "Skipping federation {federation_id}, its client failed to load: {}",
+                        status.error.as_deref().unwrap_or("unknown error")
+                    );
+                }
+                multimint.all().await
+            }
+        };
+        let req = ExportTransactionsRequest {
+            format: *format,
+            federation_id: *federation_id,
+            all_federations: federation_id.is_none(),
+            start_time: *start_time,
+            end_time: *end_time,
+        };
+        let export = export_transactions(clients, &req).await;
+        multimint.shutdown().await;
+        let export = export?;
+        match output {
+            Some(path) => std::fs::write(path, export)?,
+            None => print!("{export}"),
+        }
+        return Ok(());
     }
-}
 
-#[derive(Subcommand)]
-enum Commands {
-    Start,
-    Stop,
-}
+    if let Some(Commands::ChangePassphrase {
+        new_passphrase,
+        remove,
+    }) = &cli.command
+    {
+        let db = multimint::open_database(&config::db_path_from_cli(&cli)?)?;
+        let current = config::passphrase_from_cli(&cli)?;
+        let new = match (new_passphrase, remove) {
+            (_, true) => None,
+            (Some(new_passphrase), false) => Some(new_passphrase.clone()),
+            (None, false) => {
+                let new_passphrase = config::prompt_passphrase("New passphrase: ")?;
+                if config::prompt_passphrase("Repeat new passphrase: ")? != new_passphrase {
+                    return Err(anyhow::anyhow!("Passphrases don't match"));
+                }
+                Some(new_passphrase)
+            }
+        };
+        multimint::secret::change_passphrase(&db, current.as_deref(), new.as_deref()).await?;
+        println!(
+            "{}",
+            if new.is_some() {
+                "Mnemonic encrypted with the new passphrase"
+            } else {
+                "Mnemonic stored in plaintext"
+            }
+        );
+        return Ok(());
+    }
 
-#[derive(Parser)]
-#[clap(version = "1.0", author = "Kody Low")]
-struct Cli {
-    /// Federation invite code
-    #[clap(long, env = "FEDIMINT_CLIENTD_INVITE_CODE", required = false)]
-    invite_code: String,
+    let config = Config::from_cli(&cli)?;
+    // Fail on an invalid config before opening anything rather than at first
+    // use
+    config.check()?;
+
+    if let Some(Commands::CheckConfig) = cli.command {
+        println!("Config OK");
+        println!("  db path: {}", config.db_path.display());
+        println!("  mode: {:?}", config.mode);
+        println!("  listener: {}", config.listener);
+        println!("  api keys: {}", config.api_keys.len());
+        println!("  invite codes: {}", config.invite_codes.len());
+        if let Some(default_federation) = config.default_federation {
+            println!("  default federation: {default_federation}");
+        }
+        println!("  metrics: {}", config.prometheus_addr);
+        println!("  tenants: {}", config.tenants.len());
+        return Ok(());
+    }
 
-    /// Path to FM database
-    #[clap(long, env = "FEDIMINT_CLIENTD_DB_PATH", required = true)]
-    db_path: PathBuf,
+    init_logging(&config.logging)?;
 
-    /// Password
-    #[clap(long, env = "FEDIMINT_CLIENTD_PASSWORD", required = true)]
-    password: String,
+    let passphrase = config::passphrase_from_cli(&cli)?;
+    let mut state = AppState::new(&config, passphrase.clone()).await?;
 
-    /// Addr
-    #[clap(long, env = "FEDIMINT_CLIENTD_ADDR", required = true)]
-    addr: String,
+    if config.invite_codes.is_empty() {
+        info!("No federation invite code provided, skipping client creation");
+    }
+    join_federations(&mut state, &config.invite_codes, config.default_federation).await?;
 
-    /// Prometheus addr
-    #[clap(long, env = "PROMETHEUS_ADDR", default_value = "127.0.0.1:3001")]
-    prometheus_addr: String,
+    if state.multimint.all().await.is_empty() && state.multimint.loading().await.is_empty() {
+        return Err(anyhow::anyhow!("No clients found, must have at least one client to start the server. Try providing a federation invite code with the `--invite-code` flag or setting the `FEDIMINT_CLIENTD_INVITE_CODE` environment variable."));
+    }
 
-    /// Manual secret
-    #[clap(long, env = "FEDIMINT_CLIENTD_MANUAL_SECRET", required = false)]
-    manual_secret: Option<String>,
+    let mut tenants = Vec::new();
+    for tenant in &config.tenants {
+        info!("Opening tenant {}", tenant.name);
+        let mut tenant_state = state
+            .new_tenant(tenant.db_path(&config.db_path), passphrase.clone())
+            .await?;
+        join_federations(
+            &mut tenant_state,
+            &tenant.invite_codes,
+            tenant.default_federation,
+        )
+        .await?;
+        tenants.push((tenant.name.clone(), tenant_state));
+    }
 
-    /// Mode: ws, rest
-    #[clap(long, env = "FEDIMINT_CLIENTD_MODE", default_value = "rest")]
-    mode: Mode,
-}
+    for state in std::iter::once(&state).chain(tenants.iter().map(|(_, state)| state)) {
+        state
+            .multimint
+            .spawn_gateway_cache_refresh(Duration::from_secs(config.gateway_refresh_interval));
+        meta::spawn_meta_refresh_task(
+            state.clone(),
+            Duration::from_secs(config.meta_refresh_interval),
+        );
+        accounts::resume_pending_operations(state).await;
+    }
+    wallet_metrics::spawn_wallet_metrics_task(
+        state.clone(),
+        tenants.clone(),
+        Duration::from_secs(config.metrics_update_interval),
+    );
+
+    let mut api_keys = config.api_keys.clone();
+    api_keys.extend(
+        config
+            .tenants
+            .iter()
+            .flat_map(|tenant| tenant.api_keys.iter().cloned()),
+    );
+    let auth = BearerAuth::new(&api_keys)?;
+    let tenant_keys = TenantKeys::new(&config.tenants)?;
+    let main_server = start_main_server(
+        config.listener,
+        auth,
+        tenant_keys,
+        config.mode,
+        state.clone(),
+        tenants.clone(),
+    )
+    .map_err(|e| e.context("main server has failed"));
+    let metrics_server = start_metrics_server(&config.prometheus_addr)
+        .map_err(|e| e.context("metrics server has failed"));
 
-// const PID_FILE: &str = "/tmp/fedimint_http.pid";
+    // Dropping the servers stops accepting connections, requests already being
+    // handled keep running in their own tasks until drained below
+    let res = tokio::select! {
+        res = async { try_join!(main_server, metrics_server) } => res.map(|_| ()),
+        _ = shutdown_signal() => Ok(()),
+    };
+    if let Err(e) = &res {
+        error!("Server failed, shutting down: {e:?}");
+    }
 
-#[tokio::main]
-async fn main() -> Result<()> {
-    tracing_subscriber::fmt::init();
-    dotenv::dotenv().ok();
+    shutdown(state, tenants, Duration::from_secs(config.shutdown_timeout)).await;
+    res
+}
 
-    let cli: Cli = Cli::parse();
+/// Joins the federations in `invite_codes` and sets the default federation.
+async fn join_federations(
+    state: &mut AppState,
+    invite_codes: &[InviteCode],
+    default_federation: Option<FederationId>,
+) -> Result<()> {
+    for invite_code in invite_codes {
+        let federation_id = state.multimint.register_new(invite_code.clone()).await?;
+        info!("Created client for federation id: {:?}", federation_id);
+    }
 
-    let mut state = AppState::new(cli.db_path).await?;
+    if let Some(default_federation) = default_federation {
+        state
+            .multimint
+            .set_default_federation(default_federation)
+            .await?;
+        info!("Default federation: {default_federation}");
+    }
+    Ok(())
+}
 
-    match InviteCode::from_str(&cli.invite_code) {
-        Ok(invite_code) => {
-            let federation_id = state.multimint.register_new(invite_code).await?;
-            info!("Created client for federation id: {:?}", federation_id);
+async fn shutdown_signal() {
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            error!("Failed to listen for Ctrl+C: {e}");
+            std::future::pending::<()>().await;
         }
-        Err(e) => {
-            info!(
-                "No federation invite code provided, skipping client creation: {}",
-                e
-            );
+    };
+
+    #[cfg(unix)]
+    let terminate = async {
+        match tokio::signal::unix::signal(tokio::signal::unix::SignalKind::terminate()) {
+            Ok(mut signal) => {
+                signal.recv().await;
+            }
+            Err(e) => {
+                error!("Failed to listen for SIGTERM: {e}");
+                std::future::pending::<()>().await;
+            }
         }
-    }
+    };
 
-    if state.multimint.all().await.is_empty() {
-        return Err(anyhow::anyhow!("No clients found, must have at least one client to start the server. Try providing a federation invite code with the `--invite-code` flag or setting the `FEDIMINT_CLIENTD_INVITE_CODE` environment variable."));
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+
+    tokio::select! {
+        _ = ctrl_c => info!("Ctrl+C received. Shutting down..."),
+        _ = terminate => info!("SIGTERM received. Shutting down..."),
     }
+}
 
-    let main_server = start_main_server(&cli.addr, &cli.password, cli.mode, state)
-        .map_err(|e| e.context("main server has failed"));
-    let metrics_server = start_metrics_server(&cli.prometheus_addr)
-        .map_err(|e| e.context("metrics server has failed"));
+async fn shutdown(state: AppState, tenants: Vec<(String, AppState)>, timeout: Duration) {
+    info!("Waiting up to {timeout:?} for active requests to complete...");
+    if state.wait_for_active_requests(timeout).await {
+        info!("All active requests completed.");
+    }
+    state.shutdown().await;
+    for (name, tenant_state) in tenants {
+        info!("Shutting down tenant {name}");
+        tenant_state.shutdown().await;
+    }
+}
 
-    try_join!(main_server, metrics_server)?;
+fn init_logging(logging: &LoggingConfig) -> Result<()> {
+    let filter = match (std::env::var("RUST_LOG"), &logging.filter) {
+        (Ok(filter), _) => EnvFilter::try_new(filter)?,
+        (Err(_), Some(filter)) => EnvFilter::try_new(filter)?,
+        (Err(_), None) => EnvFilter::new("info"),
+    };
+    let subscriber = tracing_subscriber::fmt().with_env_filter(filter);
+    match logging.format {
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber.json().init(),
+    }
     Ok(())
 }
 
 async fn start_main_server(
-    addr: &str,
-    password: &str,
+    listener: Listener,
+    auth: BearerAuth,
+    tenant_keys: TenantKeys,
     mode: Mode,
     state: AppState,
+    tenants: Vec<(String, AppState)>,
 ) -> anyhow::Result<()> {
-    let app = match mode {
+    let active_requests = state.active_requests.clone();
+    let health_state = health::HealthState::new(state.clone(), tenants.clone());
+    health_state.spawn_federation_checks();
+    let mut app = match mode {
         Mode::Rest => Router::new()
             .nest("/v2", fedimint_v2_rest())
-            .with_state(state)
-            .layer(ValidateRequestHeaderLayer::bearer(password)),
+            .with_state(state),
         Mode::Ws => Router::new()
             .route("/ws", get(websocket_handler))
-            .with_state(state)
-            .layer(ValidateRequestHeaderLayer::bearer(password)),
+            .with_state(state),
     };
+    for (name, tenant_state) in tenants {
+        app = match mode {
+            Mode::Rest => app.nest(
+                &format!("/tenants/{name}/v2"),
+                fedimint_v2_rest().with_state(tenant_state),
+            ),
+            Mode::Ws => app.nest(
+                &format!("/tenants/{name}"),
+                Router::new()
+                    .route("/ws", get(websocket_handler))
+                    .with_state(tenant_state),
+            ),
+        };
+    }
+    let app = app.layer(ValidateRequestHeaderLayer::custom(auth));
     info!("Starting server in {mode:?} mode");
 
     let cors = CorsLayer::new()
@@ -148,14 +357,26 @@ async fn start_main_server(
 
     let app = app
         .layer(cors)
+        .layer(middleware::from_fn_with_state(
+            active_requests,
+            track_active_requests,
+        ))
         .layer(TraceLayer::new_for_http())
         .route("/health", get(|| async { "Server is up and running!" }))
+        .route("/health/live", get(health::live))
+        .route("/health/ready", get(health::ready).with_state(health_state))
         .route_layer(middleware::from_fn(track_metrics));
 
-    let listener = tokio::net::TcpListener::bind(addr).await?;
-    info!("fedimint-clientd listening on {addr:?}");
-    axum::serve(listener, app).await?;
-    Ok(())
+    // Tenant requests are rewritten to the tenant's routes, which has to
+    // happen before routing
+    let app = Router::new()
+        .fallback_service(app)
+        .layer(middleware::from_fn_with_state(
+            tenant_keys,
+            tenant::route_tenant,
+        ));
+
+    listener.serve(app).await
 }
 
 async fn start_metrics_server(bind: &str) -> anyhow::Result<()> {
@@ -184,6 +405,22 @@ fn setup_metrics_recorder() -> anyhow::Result<PrometheusHandle> {
         .install_recorder()?)
 }
 
+/// Rejects requests once shutting down and keeps the others counted as active
+/// until their response is ready.
+async fn track_active_requests(
+    State(active_requests): State<ActiveRequests>,
+    req: Request,
+    next: Next,
+) -> axum::response::Response {
+    match active_requests.begin() {
+        Some(_guard) => next.run(req).await,
+        None => {
+            warn!("Rejecting request, server is shutting down");
+            (StatusCode::SERVICE_UNAVAILABLE, "Server is shutting down").into_response()
+        }
+    }
+}
+
 async fn track_metrics(req: Request, next: Next) -> impl IntoResponse {
     let start = Instant::now();
     let path = if let Some(matched_path) = req.extensions().get::<MatchedPath>() {
@@ -216,10 +453,20 @@ async fn track_metrics(req: Request, next: Next) -> impl IntoResponse {
 /// - `/v2/admin/discover-version`: Discover the common api version to use to
 ///   communicate with the federation.
 /// - `/v2/admin/info`: Display wallet info (holdings, tiers).
-/// - `/v2/admin/join`: Join a federation with an invite code.
+/// - `/v2/admin/meta`: Get the federation's effective meta.
+/// - `/v2/admin/federation-status`: Reachability, latency and session count of
+///   each federation's guardians.
+/// - `/v2/admin/join`: Join a federation with an invite code, recovering
+///   previous funds if requested or a backup exists.
+/// - `/v2/admin/recovery-status`: Progress of a recovering client.
+/// - `/v2/admin/preview`: Inspect a federation from an invite code without
+///   joining it.
 /// - `/v2/admin/restore`: Restore the previously created backup of mint notes
 ///   (with `backup` command).
 /// - `/v2/admin/list-operations`: List operations.
+/// - `/v2/admin/export-transactions`: Export the transaction history as CSV or
+///   JSON Lines.
+/// - `/v2/admin/operation`: Get a single operation with its current status.
 /// - `/v2/admin/module`: Call a module subcommand.
 /// - `/v2/admin/config`: Returns the client config.
 ///
@@ -227,6 +474,10 @@ async fn track_metrics(req: Request, next: Next) -> impl IntoResponse {
 /// - `/v2/mint/reissue`: Reissue notes received from a third party to avoid
 ///   double spends.
 /// - `/v2/mint/spend`: Prepare notes to send to a third party as a payment.
+/// - `/v2/mint/spend-status`: Check whether the notes of a spend were redeemed
+///   by the recipient, refunded, or are still pending.
+/// - `/v2/mint/reclaim`: Reissue the notes of a spend back into the wallet
+///   without waiting for its timeout, unless the recipient redeemed them.
 /// - `/v2/mint/validate`: Verifies the signatures of e-cash notes, but *not* if
 ///   they have been spent already.
 /// - `/v2/mint/split`: Splits a string containing multiple e-cash notes (e.g.
@@ -243,6 +494,8 @@ async fn track_metrics(req: Request, next: Next) -> impl IntoResponse {
 /// - `/v2/ln/pay`: Pay a lightning invoice or lnurl via a gateway.
 /// - `/v2/ln/await-pay`: Wait for a lightning payment to complete.
 /// - `/v2/ln/list-gateways`: List registered gateways.
+/// - `/v2/ln/refresh-gateways`: Refresh the gateway cache and list the
+///   registered gateways.
 /// - `/v2/ln/switch-gateway`: Switch active gateway.
 ///
 /// Onchain related commands:
@@ -251,12 +504,25 @@ async fn track_metrics(req: Request, next: Next) -> impl IntoResponse {
 /// - `/v2/onchain/await-deposit`: Wait for deposit on previously generated
 ///   address.
 /// - `/v2/onchain/withdraw`: Withdraw funds from the federation.
+///
+/// Account related commands:
+/// - `/v2/accounts/create`: Create an internal account with a zero balance.
+/// - `/v2/accounts/list`: List the accounts and their balances.
+/// - `/v2/accounts/balance`: Get the balance of an account.
+/// - `/v2/accounts/history`: List the entries of an account, most recent
+///   first.
+/// - `/v2/accounts/invoice`: Create a lightning invoice that credits an account
+///   once it's paid.
+/// - `/v2/accounts/pay`: Pay a lightning invoice or lnurl from an account.
+/// - `/v2/accounts/transfer`: Move funds between two accounts.
 fn fedimint_v2_rest() -> Router<AppState> {
     let mint_router = Router::new()
         .route("/decode-notes", post(mint::decode_notes::handle_rest))
         .route("/encode-notes", post(mint::encode_notes::handle_rest))
         .route("/reissue", post(mint::reissue::handle_rest))
         .route("/spend", post(mint::spend::handle_rest))
+        .route("/spend-status", post(mint::spend_status::handle_rest))
+        .route("/reclaim", post(mint::reclaim::handle_rest))
         .route("/validate", post(mint::validate::handle_rest))
         .route("/split", post(mint::split::handle_rest))
         .route("/combine", post(mint::combine::handle_rest));
@@ -273,7 +539,8 @@ fn fedimint_v2_rest() -> Router<AppState> {
             post(ln::claim_external_receive_tweaked::handle_rest),
         )
         .route("/pay", post(ln::pay::handle_rest))
-        .route("/list-gateways", post(ln::list_gateways::handle_rest));
+        .route("/list-gateways", post(ln::list_gateways::handle_rest))
+        .route("/refresh-gateways", post(ln::refresh_gateways::handle_rest));
 
     let onchain_router = Router::new()
         .route(
@@ -291,7 +558,17 @@ fn fedimint_v2_rest() -> Router<AppState> {
         )
         .route("/federation-ids", get(admin::federation_ids::handle_rest))
         .route("/info", get(admin::info::handle_rest))
+        .route("/meta", post(admin::meta::handle_rest))
+        .route(
+            "/federation-status",
+            get(admin::federation_status::handle_rest),
+        )
         .route("/join", post(admin::join::handle_rest))
+        .route("/preview", post(admin::preview::handle_rest))
+        .route(
+            "/recovery-status",
+            post(admin::recovery_status::handle_rest),
+        )
         .route("/restore", post(admin::restore::handle_rest))
         // .route("/printsecret", get(handle_printsecret)) TODO: should I expose this
         // under admin?
@@ -299,12 +576,27 @@ fn fedimint_v2_rest() -> Router<AppState> {
             "/list-operations",
             post(admin::list_operations::handle_rest),
         )
+        .route(
+            "/export-transactions",
+            post(admin::export_transactions::handle_rest),
+        )
+        .route("/operation", post(admin::operation::handle_rest))
         .route("/module", post(admin::module::handle_rest))
         .route("/config", get(admin::config::handle_rest));
 
+    let accounts_router = Router::new()
+        .route("/create", post(accounts::create::handle_rest))
+        .route("/list", get(accounts::list::handle_rest))
+        .route("/balance", post(accounts::balance::handle_rest))
+        .route("/history", post(accounts::history::handle_rest))
+        .route("/invoice", post(accounts::invoice::handle_rest))
+        .route("/pay", post(accounts::pay::handle_rest))
+        .route("/transfer", post(accounts::transfer::handle_rest));
+
     Router::new()
         .nest("/admin", admin_router)
         .nest("/mint", mint_router)
         .nest("/ln", ln_router)
         .nest("/onchain", onchain_router)
+        .nest("/accounts", accounts_router)
 }
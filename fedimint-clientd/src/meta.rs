@@ -0,0 +1,267 @@
+//! Effective federation meta.
+//!
+//! Federations publish meta in their config's `global.meta` map, in the
+//! consensus value of the meta module if they run one (with the `meta`
+//! feature), and optionally in a JSON document at the `meta_external_url`
+//! given in either. The external document is either a map of meta fields or,
+//! as published by most federations, a map from federation id to such a map.
+//! Fields from the meta module take precedence over the config, and fields from
+//! the external document over both.
+//!
+//! The result is cached per federation and refreshed by a background task.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use std::time::Duration;
+
+use anyhow::{anyhow, Result};
+use multimint::fedimint_client::ClientHandleArc;
+use multimint::fedimint_core::config::FederationId;
+use multimint::fedimint_core::secp256k1::PublicKey;
+use multimint::fedimint_core::time::duration_since_epoch;
+use multimint::fedimint_core::Amount;
+use serde::Serialize;
+use serde_json::Value;
+use tokio::sync::RwLock;
+use tracing::{debug, warn};
+
+use crate::state::AppState;
+
+const META_EXTERNAL_URL: &str = "meta_external_url";
+
+/// How long to wait for the external meta document
+const FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FederationMeta {
+    pub federation_id: FederationId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub federation_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub federation_icon_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub welcome_message: Option<String>,
+    /// Largest balance the federation wants a single user to hold
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_balance_msats: Option<Amount>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_invoice_msats: Option<Amount>,
+    /// Gateways the guardians vouch for
+    pub vetted_gateways: Vec<PublicKey>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub popup: Option<Popup>,
+    /// Unix timestamp at which the federation plans to shut down
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub federation_expiry_timestamp: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta_external_url: Option<String>,
+    /// Fields without a typed counterpart above
+    pub other: BTreeMap<String, String>,
+    /// Unix timestamp of the last refresh
+    pub fetched_at: u64,
+    /// Why the external meta couldn't be fetched, in which case only the
+    /// config's meta is used
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub external_error: Option<String>,
+    /// Why the meta module's consensus value couldn't be read
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub module_error: Option<String>,
+}
+
+/// Message the federation wants shown to its users, e.g. before shutting
+/// down
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Popup {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub end_timestamp: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub countdown_message: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ended_message: Option<String>,
+}
+
+impl FederationMeta {
+    fn from_fields(
+        federation_id: FederationId,
+        mut fields: BTreeMap<String, String>,
+        external_error: Option<String>,
+        module_error: Option<String>,
+    ) -> Self {
+        let mut take = |key: &str| fields.remove(key).filter(|value| !value.is_empty());
+        let parse_u64 = |value: Option<String>| value.and_then(|value| value.parse::<u64>().ok());
+
+        let federation_name = take("federation_name");
+        let federation_icon_url = take("federation_icon_url");
+        let welcome_message = take("welcome_message");
+        let max_balance_msats = parse_u64(take("max_balance_msats")).map(Amount::from_msats);
+        let max_invoice_msats = parse_u64(take("max_invoice_msats")).map(Amount::from_msats);
+        let vetted_gateways = take("vetted_gateways")
+            .and_then(|gateways| serde_json::from_str::<Vec<PublicKey>>(&gateways).ok())
+            .unwrap_or_default();
+        let popup_end_timestamp = parse_u64(take("popup_end_timestamp"));
+        let popup_countdown_message = take("popup_countdown_message");
+        let popup_ended_message = take("popup_ended_message");
+        let federation_expiry_timestamp = parse_u64(take("federation_expiry_timestamp"));
+        let meta_external_url = take(META_EXTERNAL_URL);
+
+        let popup = (popup_end_timestamp.is_some()
+            || popup_countdown_message.is_some()
+            || popup_ended_message.is_some())
+        .then_some(Popup {
+            end_timestamp: popup_end_timestamp,
+            countdown_message: popup_countdown_message,
+            ended_message: popup_ended_message,
+        });
+
+        FederationMeta {
+            federation_id,
+            federation_name,
+            federation_icon_url,
+            welcome_message,
+            max_balance_msats,
+            max_invoice_msats,
+            vetted_gateways,
+            popup,
+            federation_expiry_timestamp,
+            meta_external_url,
+            other: fields,
+            fetched_at: duration_since_epoch().as_secs(),
+            external_error,
+            module_error,
+        }
+    }
+}
+
+/// Meta values are strings, but external documents often use JSON numbers or
+/// arrays, which are kept in their JSON representation.
+fn to_fields(document: serde_json::Map<String, Value>) -> BTreeMap<String, String> {
+    document
+        .into_iter()
+        .map(|(key, value)| match value {
+            Value::String(value) => (key, value),
+            value => (key, value.to_string()),
+        })
+        .collect()
+}
+
+/// Reads the meta fields agreed on by the guardians through the meta module,
+/// `None` if the federation doesn't run it or hasn't set a value yet.
+#[cfg(feature = "meta")]
+async fn module_fields(client: &ClientHandleArc) -> Result<Option<BTreeMap<String, String>>> {
+    use multimint::fedimint_meta_client::common::{DEFAULT_META_KEY, KIND};
+    use multimint::fedimint_meta_client::MetaClientModule;
+
+    if client.get_first_instance(&KIND).is_none() {
+        return Ok(None);
+    }
+    let Some(consensus) = client
+        .get_first_module::<MetaClientModule>()
+        .get_consensus_value(DEFAULT_META_KEY)
+        .await?
+    else {
+        return Ok(None);
+    };
+    match consensus.value.to_json()? {
+        Value::Object(fields) => Ok(Some(to_fields(fields))),
+        _ => Err(anyhow!("Meta module value is not an object")),
+    }
+}
+
+#[cfg(not(feature = "meta"))]
+async fn module_fields(_client: &ClientHandleArc) -> Result<Option<BTreeMap<String, String>>> {
+    Ok(None)
+}
+
+/// Caches the effective meta of each federation.
+#[derive(Debug, Clone, Default)]
+pub struct MetaCache {
+    http: reqwest::Client,
+    entries: Arc<RwLock<BTreeMap<FederationId, FederationMeta>>>,
+}
+
+impl MetaCache {
+    async fn fetch_external(
+        &self,
+        federation_id: FederationId,
+        url: &str,
+    ) -> Result<BTreeMap<String, String>> {
+        let document = self
+            .http
+            .get(url)
+            .timeout(FETCH_TIMEOUT)
+            .send()
+            .await?
+            .error_for_status()?
+            .json::<serde_json::Map<String, Value>>()
+            .await?;
+
+        match document.get(&federation_id.to_string()) {
+            Some(Value::Object(fields)) => Ok(to_fields(fields.clone())),
+            Some(_) => Err(anyhow!("Meta for {federation_id} is not an object")),
+            None => Ok(to_fields(document)),
+        }
+    }
+
+    /// Fetches the effective meta of `client`'s federation and caches it.
+    pub async fn refresh(&self, client: &ClientHandleArc) -> FederationMeta {
+        let federation_id = client.federation_id();
+        let mut fields = client.config().await.global.meta.clone();
+
+        let mut module_error = None;
+        match module_fields(client).await {
+            Ok(Some(module)) => fields.extend(module),
+            Ok(None) => {}
+            Err(e) => {
+                warn!("Failed to read the meta module of {federation_id}: {e}");
+                module_error = Some(e.to_string());
+            }
+        }
+
+        let mut external_error = None;
+        if let Some(url) = fields.get(META_EXTERNAL_URL).cloned() {
+            match self.fetch_external(federation_id, &url).await {
+                Ok(external) => fields.extend(external),
+                Err(e) => {
+                    warn!("Failed to fetch external meta for {federation_id} from {url}: {e}");
+                    external_error = Some(e.to_string());
+                }
+            }
+        }
+
+        let meta = FederationMeta::from_fields(federation_id, fields, external_error, module_error);
+        self.entries
+            .write()
+            .await
+            .insert(federation_id, meta.clone());
+        meta
+    }
+
+    /// Returns the cached meta of `client`'s federation, fetching it if it
+    /// isn't cached yet or `refresh` is set.
+    pub async fn get(&self, client: &ClientHandleArc, refresh: bool) -> FederationMeta {
+        if !refresh {
+            if let Some(meta) = self.entries.read().await.get(&client.federation_id()) {
+                return meta.clone();
+            }
+        }
+        self.refresh(client).await
+    }
+}
+
+/// Spawns the task refreshing the meta of every federation every `interval`.
+pub fn spawn_meta_refresh_task(state: AppState, interval: Duration) {
+    tokio::spawn(async move {
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+            debug!("Refreshing federation meta");
+            for client in state.multimint.all().await {
+                state.meta_cache.refresh(&client).await;
+            }
+        }
+    });
+}
@@ -0,0 +1,48 @@
+use anyhow::anyhow;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::AccountResponse;
+use crate::error::AppError;
+use crate::state::AppState;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct BalanceRequest {
+    pub name: String,
+}
+
+async fn _balance(state: &AppState, req: BalanceRequest) -> Result<AccountResponse, AppError> {
+    let account = state
+        .multimint
+        .accounts()
+        .get(&req.name)
+        .await
+        .ok_or_else(|| {
+            AppError::new(
+                StatusCode::NOT_FOUND,
+                anyhow!("Account {} not found", req.name),
+            )
+        })?;
+    Ok(AccountResponse::new(req.name, account))
+}
+
+pub async fn handle_ws(state: AppState, v: Value) -> Result<Value, AppError> {
+    let v = serde_json::from_value::<BalanceRequest>(v)
+        .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, anyhow!("Invalid request: {}", e)))?;
+    let balance = _balance(&state, v).await?;
+    let balance_json = json!(balance);
+    Ok(balance_json)
+}
+
+#[axum_macros::debug_handler]
+pub async fn handle_rest(
+    State(state): State<AppState>,
+    Json(req): Json<BalanceRequest>,
+) -> Result<Json<AccountResponse>, AppError> {
+    let balance = _balance(&state, req).await?;
+    Ok(Json(balance))
+}
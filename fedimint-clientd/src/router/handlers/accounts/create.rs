@@ -0,0 +1,49 @@
+use anyhow::anyhow;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::AccountResponse;
+use crate::error::AppError;
+use crate::state::AppState;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct CreateAccountRequest {
+    pub name: String,
+}
+
+async fn _create(state: &AppState, req: CreateAccountRequest) -> Result<AccountResponse, AppError> {
+    if req.name.is_empty() {
+        return Err(AppError::new(
+            StatusCode::BAD_REQUEST,
+            anyhow!("Account name can't be empty"),
+        ));
+    }
+    let account = state
+        .multimint
+        .accounts()
+        .create(&req.name)
+        .await
+        .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, e))?;
+    Ok(AccountResponse::new(req.name, account))
+}
+
+pub async fn handle_ws(state: AppState, v: Value) -> Result<Value, AppError> {
+    let v = serde_json::from_value::<CreateAccountRequest>(v)
+        .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, anyhow!("Invalid request: {}", e)))?;
+    let account = _create(&state, v).await?;
+    let account_json = json!(account);
+    Ok(account_json)
+}
+
+#[axum_macros::debug_handler]
+pub async fn handle_rest(
+    State(state): State<AppState>,
+    Json(req): Json<CreateAccountRequest>,
+) -> Result<Json<AccountResponse>, AppError> {
+    let account = _create(&state, req).await?;
+    Ok(Json(account))
+}
@@ -0,0 +1,48 @@
+use anyhow::anyhow;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::AccountEntryResponse;
+use crate::error::AppError;
+use crate::state::AppState;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryRequest {
+    pub name: String,
+    /// Only return the most recent entries
+    pub limit: Option<usize>,
+}
+
+async fn _history(
+    state: &AppState,
+    req: HistoryRequest,
+) -> Result<Vec<AccountEntryResponse>, AppError> {
+    let entries = state
+        .multimint
+        .accounts()
+        .history(&req.name, req.limit)
+        .await
+        .map_err(|e| AppError::new(StatusCode::NOT_FOUND, e))?;
+    Ok(entries.into_iter().map(Into::into).collect())
+}
+
+pub async fn handle_ws(state: AppState, v: Value) -> Result<Value, AppError> {
+    let v = serde_json::from_value::<HistoryRequest>(v)
+        .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, anyhow!("Invalid request: {}", e)))?;
+    let history = _history(&state, v).await?;
+    let history_json = json!(history);
+    Ok(history_json)
+}
+
+#[axum_macros::debug_handler]
+pub async fn handle_rest(
+    State(state): State<AppState>,
+    Json(req): Json<HistoryRequest>,
+) -> Result<Json<Vec<AccountEntryResponse>>, AppError> {
+    let history = _history(&state, req).await?;
+    Ok(Json(history))
+}
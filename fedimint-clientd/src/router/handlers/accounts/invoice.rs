@@ -0,0 +1,68 @@
+use anyhow::anyhow;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use multimint::db::AccountOperation;
+use multimint::fedimint_core::config::FederationId;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use super::watch_deposit;
+use crate::error::AppError;
+use crate::router::handlers::ln::invoice::{_invoice, LnInvoiceRequest, LnInvoiceResponse};
+use crate::state::AppState;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountInvoiceRequest {
+    /// Account to credit once the invoice is paid
+    pub name: String,
+    #[serde(flatten)]
+    pub invoice: LnInvoiceRequest,
+}
+
+async fn _account_invoice(
+    state: &AppState,
+    req: AccountInvoiceRequest,
+) -> Result<LnInvoiceResponse, AppError> {
+    if state.multimint.accounts().get(&req.name).await.is_none() {
+        return Err(AppError::new(
+            StatusCode::NOT_FOUND,
+            anyhow!("Account {} not found", req.name),
+        ));
+    }
+    let client = state.get_client(req.invoice.federation_id).await?;
+    let federation_id: FederationId = client.federation_id();
+    let amount = req.invoice.amount_msat;
+    let invoice = _invoice(state, client, req.invoice).await?;
+
+    let deposit = AccountOperation {
+        account: req.name,
+        federation_id,
+        amount,
+    };
+    state
+        .multimint
+        .accounts()
+        .add_pending_deposit(invoice.operation_id, deposit.clone())
+        .await?;
+    watch_deposit(state.clone(), invoice.operation_id, deposit);
+    Ok(invoice)
+}
+
+pub async fn handle_ws(state: AppState, v: Value) -> Result<Value, AppError> {
+    let v = serde_json::from_value::<AccountInvoiceRequest>(v)
+        .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, anyhow!("Invalid request: {}", e)))?;
+    let invoice = _account_invoice(&state, v).await?;
+    let invoice_json = json!(invoice);
+    Ok(invoice_json)
+}
+
+#[axum_macros::debug_handler]
+pub async fn handle_rest(
+    State(state): State<AppState>,
+    Json(req): Json<AccountInvoiceRequest>,
+) -> Result<Json<LnInvoiceResponse>, AppError> {
+    let invoice = _account_invoice(&state, req).await?;
+    Ok(Json(invoice))
+}
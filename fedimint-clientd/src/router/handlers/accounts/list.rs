@@ -0,0 +1,32 @@
+use axum::extract::State;
+use axum::Json;
+use serde_json::{json, Value};
+
+use super::AccountResponse;
+use crate::error::AppError;
+use crate::state::AppState;
+
+async fn _list(state: &AppState) -> Result<Vec<AccountResponse>, AppError> {
+    Ok(state
+        .multimint
+        .accounts()
+        .list()
+        .await
+        .into_iter()
+        .map(|(name, account)| AccountResponse::new(name, account))
+        .collect())
+}
+
+pub async fn handle_ws(state: AppState) -> Result<Value, AppError> {
+    let accounts = _list(&state).await?;
+    let accounts_json = json!(accounts);
+    Ok(accounts_json)
+}
+
+#[axum_macros::debug_handler]
+pub async fn handle_rest(
+    State(state): State<AppState>,
+) -> Result<Json<Vec<AccountResponse>>, AppError> {
+    let accounts = _list(&state).await?;
+    Ok(Json(accounts))
+}
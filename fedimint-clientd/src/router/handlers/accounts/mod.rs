@@ -0,0 +1,240 @@
+use std::time::Duration;
+
+use multimint::db::{Account, AccountEntry, AccountEntryKind, AccountOperation};
+use multimint::fedimint_client::ClientHandleArc;
+use multimint::fedimint_core::config::FederationId;
+use multimint::fedimint_core::core::OperationId;
+use multimint::fedimint_core::Amount;
+use multimint::fedimint_ln_client::LnReceiveState;
+use serde::Serialize;
+use serde_json::Value;
+use tracing::{info, warn};
+
+use super::admin::operation::{current_state, operation_amounts};
+use super::admin::OperationStatus;
+use super::ln::await_invoice::{AwaitInvoiceRequest, InvoiceStatus};
+use crate::error::AppError;
+use crate::state::AppState;
+
+pub mod balance;
+pub mod create;
+pub mod history;
+pub mod invoice;
+pub mod list;
+pub mod pay;
+pub mod transfer;
+
+/// How long to wait between attempts to get the client of a pending deposit
+/// or payment
+const CLIENT_RETRY_DELAY: Duration = Duration::from_secs(5);
+
+/// How long to wait between checks of a pending payment's operation
+const PAYMENT_POLL_INTERVAL: Duration = Duration::from_secs(30);
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountResponse {
+    pub name: String,
+    pub balance_msat: Amount,
+    pub created_at: u64,
+}
+
+impl AccountResponse {
+    fn new(name: String, account: Account) -> Self {
+        Self {
+            name,
+            balance_msat: account.balance,
+            created_at: account.created_at,
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountEntryResponse {
+    pub timestamp: u64,
+    pub kind: AccountEntryKind,
+    pub amount_msat: Amount,
+    pub balance_msat: Amount,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub federation_id: Option<FederationId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub operation_id: Option<OperationId>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub counterparty: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+}
+
+impl From<AccountEntry> for AccountEntryResponse {
+    fn from(entry: AccountEntry) -> Self {
+        Self {
+            timestamp: entry.timestamp,
+            kind: entry.kind,
+            amount_msat: entry.amount,
+            balance_msat: entry.balance,
+            federation_id: entry.federation_id,
+            operation_id: entry.operation_id,
+            counterparty: entry.counterparty,
+            description: entry.description,
+        }
+    }
+}
+
+/// Gets the client of a pending deposit or payment, waiting while it's
+/// loading or recovering.
+async fn wait_for_client(
+    state: &AppState,
+    federation_id: FederationId,
+) -> Result<ClientHandleArc, AppError> {
+    loop {
+        match state.get_client(Some(federation_id)).await {
+            Ok(client) => return Ok(client),
+            Err(_)
+                if state.multimint.has(&federation_id).await
+                    || state.multimint.is_loading(&federation_id).await =>
+            {
+                tokio::time::sleep(CLIENT_RETRY_DELAY).await;
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Waits for the invoice of a pending deposit in the background and credits
+/// its account once the invoice is paid.
+pub fn watch_deposit(state: AppState, operation_id: OperationId, deposit: AccountOperation) {
+    tokio::spawn(async move {
+        let client = match wait_for_client(&state, deposit.federation_id).await {
+            Ok(client) => client,
+            Err(e) => {
+                warn!(
+                    "Dropping deposit {operation_id:?} to account {}: {e}",
+                    deposit.account
+                );
+                return;
+            }
+        };
+
+        let req = AwaitInvoiceRequest {
+            operation_id,
+            federation_id: Some(deposit.federation_id),
+        };
+        let paid = match super::ln::await_invoice::_await_invoice(client, req).await {
+            Ok(res) => match res.status {
+                InvoiceStatus::Lnv1(status) => matches!(status, LnReceiveState::Claimed),
+                #[cfg(feature = "lnv2")]
+                InvoiceStatus::Lnv2(status) => matches!(
+                    status,
+                    multimint::fedimint_lnv2_client::ReceiveState::Claimed
+                ),
+            },
+            Err(e) => {
+                warn!("Failed to await deposit {operation_id:?}: {e}");
+                return;
+            }
+        };
+
+        match state
+            .multimint
+            .accounts()
+            .settle_pending_deposit(operation_id, paid)
+            .await
+        {
+            Ok(Some(entry)) => info!("Credited {} to account {}", entry.amount, deposit.account),
+            Ok(None) => {}
+            Err(e) => warn!("Failed to settle deposit {operation_id:?}: {e:?}"),
+        }
+    });
+}
+
+/// Settles a pending payment if its operation finished, refunding its
+/// account if the funds returned to the wallet and the part of the fee
+/// reserve above the actual fee if it succeeded. Payments that failed stay
+/// debited for an operator to review. Returns `false` while the operation is
+/// still pending.
+pub(crate) async fn settle_payment(
+    state: &AppState,
+    client: &ClientHandleArc,
+    operation_id: OperationId,
+    payment: &AccountOperation,
+) -> bool {
+    let Some(entry) = client.operation_log().get_operation(operation_id).await else {
+        warn!("Pending payment {operation_id:?} has no operation");
+        return false;
+    };
+    let current = match current_state(client, operation_id, &entry).await {
+        Ok(current) => current,
+        Err(e) => {
+            warn!("Failed to get the state of payment {operation_id:?}: {e:?}");
+            return false;
+        }
+    };
+    // Subscribing may have stored the final outcome, so read it again
+    let outcome = client
+        .operation_log()
+        .get_operation(operation_id)
+        .await
+        .and_then(|entry| entry.outcome::<Value>());
+    let spent = match OperationStatus::from_state(&entry, outcome.as_ref().or(current.as_ref())) {
+        OperationStatus::Pending => return false,
+        OperationStatus::Refunded => Amount::ZERO,
+        // An LNv2 payment's fee is only known from its contract, the whole
+        // debit is kept if the amounts are unknown
+        OperationStatus::Succeeded => match operation_amounts(&entry) {
+            (Some(amount), Some(fee)) => amount + fee,
+            _ => payment.amount,
+        },
+        OperationStatus::Failed => {
+            warn!(
+                "Payment {operation_id:?} of account {} failed without a refund, its {} stay debited until reviewed",
+                payment.account, payment.amount
+            );
+            return true;
+        }
+    };
+
+    match state
+        .multimint
+        .accounts()
+        .settle_pending_payment(operation_id, spent)
+        .await
+    {
+        Ok(Some(entry)) => info!("Refunded {} to account {}", entry.amount, payment.account),
+        Ok(None) => {}
+        Err(e) => warn!("Failed to settle payment {operation_id:?}: {e:?}"),
+    }
+    true
+}
+
+/// Waits for a pending payment's operation to finish in the background and
+/// settles it, see `settle_payment`.
+pub fn watch_payment(state: AppState, operation_id: OperationId, payment: AccountOperation) {
+    tokio::spawn(async move {
+        let client = match wait_for_client(&state, payment.federation_id).await {
+            Ok(client) => client,
+            Err(e) => {
+                // The debit is kept until the federation is joined again
+                warn!(
+                    "Can't settle payment {operation_id:?} of account {}: {e}",
+                    payment.account
+                );
+                return;
+            }
+        };
+        while !settle_payment(&state, &client, operation_id, &payment).await {
+            tokio::time::sleep(PAYMENT_POLL_INTERVAL).await;
+        }
+    });
+}
+
+/// Resumes waiting for the pending deposits and payments of a previous run.
+pub async fn resume_pending_operations(state: &AppState) {
+    let accounts = state.multimint.accounts();
+    for (operation_id, deposit) in accounts.pending_deposits().await {
+        watch_deposit(state.clone(), operation_id, deposit);
+    }
+    for (operation_id, payment) in accounts.pending_payments().await {
+        watch_payment(state.clone(), operation_id, payment);
+    }
+}
@@ -0,0 +1,162 @@
+use anyhow::anyhow;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use multimint::accounts::EntryDetails;
+use multimint::db::{AccountEntryKind, AccountOperation};
+use multimint::fedimint_core::Amount;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::warn;
+
+use super::{settle_payment, watch_payment};
+use crate::error::AppError;
+use crate::router::handlers::ln::get_invoice;
+use crate::router::handlers::ln::pay::{start_pay, wait_for_pay, LnPayRequest, LnPayResponse};
+use crate::state::AppState;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountPayRequest {
+    /// Account to debit for the payment
+    pub name: String,
+    #[serde(flatten)]
+    pub payment: LnPayRequest,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AccountPayResponse {
+    #[serde(flatten)]
+    pub payment: LnPayResponse,
+    pub balance_msat: Amount,
+}
+
+/// Fee reserved on top of an invoice's amount while it's paid from an
+/// account, matching the LNv2 module's send fee limit: a base fee plus parts
+/// per million of the amount
+const FEE_RESERVE_BASE: Amount = Amount::from_msats(100_000);
+const FEE_RESERVE_PPM: u64 = 15_000;
+
+fn fee_reserve(amount: Amount) -> Amount {
+    FEE_RESERVE_BASE + Amount::from_msats(amount.msats * FEE_RESERVE_PPM / 1_000_000)
+}
+
+async fn _account_pay(
+    state: &AppState,
+    req: AccountPayRequest,
+) -> Result<AccountPayResponse, AppError> {
+    let client = state.get_client(req.payment.federation_id).await?;
+    let bolt11 = get_invoice(&req.payment)
+        .await
+        .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, e))?;
+    let amount = Amount::from_msats(bolt11.amount_milli_satoshis().ok_or_else(|| {
+        AppError::new(
+            StatusCode::BAD_REQUEST,
+            anyhow!("We don't support invoices without an amount"),
+        )
+    })?);
+
+    // Debit the account, including the largest fee the payment may take,
+    // before paying so concurrent payments can't overdraw it
+    let reserve = fee_reserve(amount);
+    let accounts = state.multimint.accounts();
+    let details = EntryDetails {
+        federation_id: Some(client.federation_id()),
+        operation_id: None,
+        description: Some(bolt11.to_string()),
+    };
+    accounts
+        .record(
+            &req.name,
+            amount + reserve,
+            AccountEntryKind::Payment,
+            details.clone(),
+        )
+        .await
+        .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, e))?;
+
+    // The lnurl, if any, is already resolved to an invoice
+    let pay_req = LnPayRequest {
+        payment_info: bolt11.to_string(),
+        amount_msat: None,
+        lnurl_comment: None,
+        ..req.payment
+    };
+    let payment = match start_pay(state, &client, pay_req, Some(reserve)).await {
+        Ok(payment) => payment,
+        Err(e) => {
+            // No funds left the wallet
+            if let Err(refund_err) = accounts
+                .record(
+                    &req.name,
+                    amount + reserve,
+                    AccountEntryKind::Refund,
+                    details,
+                )
+                .await
+            {
+                warn!(
+                    "Failed to refund {} to account {}: {refund_err:?}",
+                    amount + reserve,
+                    req.name
+                );
+            }
+            return Err(e);
+        }
+    };
+
+    // The payment's funds stay debited until its outcome is known, only the
+    // part of the reserve above the actual fee is refunded right away
+    let operation_id = payment.operation_id;
+    let unused = match payment.fee {
+        Some(fee) => reserve.checked_sub(fee).unwrap_or_else(|| {
+            warn!("Fee {fee} of payment {operation_id:?} exceeds the reserved {reserve}");
+            Amount::ZERO
+        }),
+        None => Amount::ZERO,
+    };
+    let pending = AccountOperation {
+        account: req.name.clone(),
+        federation_id: client.federation_id(),
+        amount: amount + reserve - unused,
+    };
+    if let Err(e) = accounts
+        .add_pending_payment(operation_id, pending.clone(), unused)
+        .await
+    {
+        warn!("Failed to record pending payment {operation_id:?}: {e:?}");
+    }
+
+    let result = wait_for_pay(&client, payment).await;
+    if !settle_payment(state, &client, operation_id, &pending).await {
+        // Settled once the operation finishes, e.g. when a refund completes
+        watch_payment(state.clone(), operation_id, pending);
+    }
+    let payment = result?;
+    let balance_msat = accounts
+        .get(&req.name)
+        .await
+        .map_or(Amount::ZERO, |account| account.balance);
+    Ok(AccountPayResponse {
+        payment,
+        balance_msat,
+    })
+}
+
+pub async fn handle_ws(state: AppState, v: Value) -> Result<Value, AppError> {
+    let v = serde_json::from_value::<AccountPayRequest>(v)
+        .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, anyhow!("Invalid request: {}", e)))?;
+    let payment = _account_pay(&state, v).await?;
+    let payment_json = json!(payment);
+    Ok(payment_json)
+}
+
+#[axum_macros::debug_handler]
+pub async fn handle_rest(
+    State(state): State<AppState>,
+    Json(req): Json<AccountPayRequest>,
+) -> Result<Json<AccountPayResponse>, AppError> {
+    let payment = _account_pay(&state, req).await?;
+    Ok(Json(payment))
+}
@@ -0,0 +1,57 @@
+use anyhow::anyhow;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use multimint::fedimint_core::Amount;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::AccountEntryResponse;
+use crate::error::AppError;
+use crate::state::AppState;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferRequest {
+    pub from: String,
+    pub to: String,
+    pub amount_msat: Amount,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TransferResponse {
+    pub from: AccountEntryResponse,
+    pub to: AccountEntryResponse,
+}
+
+async fn _transfer(state: &AppState, req: TransferRequest) -> Result<TransferResponse, AppError> {
+    let (from, to) = state
+        .multimint
+        .accounts()
+        .transfer(&req.from, &req.to, req.amount_msat, req.description)
+        .await
+        .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, e))?;
+    Ok(TransferResponse {
+        from: from.into(),
+        to: to.into(),
+    })
+}
+
+pub async fn handle_ws(state: AppState, v: Value) -> Result<Value, AppError> {
+    let v = serde_json::from_value::<TransferRequest>(v)
+        .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, anyhow!("Invalid request: {}", e)))?;
+    let transfer = _transfer(&state, v).await?;
+    let transfer_json = json!(transfer);
+    Ok(transfer_json)
+}
+
+#[axum_macros::debug_handler]
+pub async fn handle_rest(
+    State(state): State<AppState>,
+    Json(req): Json<TransferRequest>,
+) -> Result<Json<TransferResponse>, AppError> {
+    let transfer = _transfer(&state, req).await?;
+    Ok(Json(transfer))
+}
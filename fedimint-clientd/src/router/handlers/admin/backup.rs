@@ -17,7 +17,7 @@ use crate::state::AppState;
 #[serde(rename_all = "camelCase")]
 pub struct BackupRequest {
     pub metadata: BTreeMap<String, String>,
-    pub federation_id: FederationId,
+    pub federation_id: Option<FederationId>,
 }
 
 async fn _backup(client: ClientHandleArc, req: BackupRequest) -> Result<(), AppError> {
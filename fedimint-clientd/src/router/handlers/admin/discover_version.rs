@@ -9,12 +9,17 @@ use crate::error::AppError;
 use crate::state::AppState;
 
 async fn _discover_version(multimint: MultiMint) -> Result<Value, AppError> {
+    let clients = multimint.all().await;
+    let versions = futures::future::join_all(
+        clients
+            .iter()
+            .map(|client| client.load_and_refresh_common_api_version()),
+    )
+    .await;
+
     let mut api_versions = HashMap::new();
-    for (id, client) in multimint.clients.lock().await.iter() {
-        api_versions.insert(
-            *id,
-            json!({"version" : client.load_and_refresh_common_api_version().await?}),
-        );
+    for (client, version) in clients.iter().zip(versions) {
+        api_versions.insert(client.federation_id(), json!({"version" : version?}));
     }
     Ok(json!(api_versions))
 }
@@ -0,0 +1,285 @@
+use std::str::FromStr;
+
+use anyhow::anyhow;
+use axum::extract::State;
+use axum::http::{header, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use multimint::fedimint_client::ClientHandleArc;
+use multimint::fedimint_core::config::FederationId;
+use multimint::fedimint_core::core::OperationId;
+use multimint::fedimint_core::Amount;
+use multimint::fedimint_ln_client::{LightningOperationMeta, LightningOperationMetaVariant};
+use multimint::fedimint_mint_client::{MintOperationMeta, MintOperationMetaVariant};
+use multimint::fedimint_wallet_client::{
+    DepositStateV2, WalletOperationMeta, WalletOperationMetaVariant,
+};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::list_operations::{
+    format_creation_time, list_filtered, unix_to_system_time, ListedOperation, OperationFilter,
+};
+use super::operation::operation_amounts;
+use super::OperationStatus;
+use crate::error::AppError;
+use crate::state::AppState;
+
+/// Named like the fields of the JSON Lines export
+const CSV_HEADER: &str =
+    "timestamp,federationId,direction,kind,amountMsat,feeMsat,counterparty,operationId,status";
+
+#[derive(Debug, Clone, Copy, Default, Deserialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum ExportFormat {
+    #[default]
+    Csv,
+    Jsonl,
+}
+
+impl FromStr for ExportFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "csv" => Ok(ExportFormat::Csv),
+            "jsonl" => Ok(ExportFormat::Jsonl),
+            _ => Err(anyhow!("Invalid export format, expected csv or jsonl")),
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ExportTransactionsRequest {
+    #[serde(default)]
+    pub format: ExportFormat,
+    pub federation_id: Option<FederationId>,
+    /// Export the transactions of all federations instead of one
+    #[serde(default)]
+    pub all_federations: bool,
+    /// Only include transactions created at or after this unix timestamp
+    pub start_time: Option<u64>,
+    /// Only include transactions created before this unix timestamp
+    pub end_time: Option<u64>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Direction {
+    In,
+    Out,
+}
+
+/// One row of the exported ledger.
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LedgerEntry {
+    pub timestamp: String,
+    pub federation_id: FederationId,
+    pub direction: Option<Direction>,
+    pub kind: String,
+    pub amount_msat: Option<u64>,
+    pub fee_msat: Option<u64>,
+    pub counterparty: Option<String>,
+    pub operation_id: OperationId,
+    pub status: OperationStatus,
+}
+
+impl LedgerEntry {
+    fn to_csv_row(&self) -> String {
+        let direction = match self.direction {
+            Some(Direction::In) => "in",
+            Some(Direction::Out) => "out",
+            None => "",
+        };
+        let optional = |value: Option<u64>| value.map(|v| v.to_string()).unwrap_or_default();
+        let status = serde_json::to_value(self.status)
+            .ok()
+            .and_then(|status| status.as_str().map(str::to_owned))
+            .unwrap_or_default();
+
+        [
+            self.timestamp.clone(),
+            self.federation_id.to_string(),
+            direction.to_string(),
+            self.kind.clone(),
+            optional(self.amount_msat),
+            optional(self.fee_msat),
+            self.counterparty.clone().unwrap_or_default(),
+            self.operation_id.fmt_full().to_string(),
+            status,
+        ]
+        .iter()
+        .map(|field| csv_escape(field))
+        .collect::<Vec<_>>()
+        .join(",")
+    }
+}
+
+/// Quotes fields containing separators, and prefixes fields that spreadsheet
+/// applications would evaluate as a formula with a `'`.
+fn csv_escape(field: &str) -> String {
+    let field = if field.starts_with(['=', '+', '-', '@', '\t', '\r']) {
+        format!("'{field}")
+    } else {
+        field.to_owned()
+    };
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field
+    }
+}
+
+/// Deposits only learn their amount once the deposit transaction is seen, so
+/// it is taken from the final outcome or else the current state of the
+/// deposit.
+fn deposited_amount(operation: &ListedOperation) -> Option<Amount> {
+    let state = operation
+        .outcome()
+        .or_else(|| operation.state.clone())
+        .and_then(|state| serde_json::from_value::<DepositStateV2>(state).ok())?;
+    match state {
+        DepositStateV2::WaitingForConfirmation { btc_deposited, .. }
+        | DepositStateV2::Confirmed { btc_deposited, .. }
+        | DepositStateV2::Claimed { btc_deposited, .. } => {
+            Some(Amount::from_sats(btc_deposited.to_sat()))
+        }
+        DepositStateV2::WaitingForTransaction | DepositStateV2::Failed(_) => None,
+    }
+}
+
+/// Normalizes an operation log entry into a ledger row.
+fn ledger_entry(
+    federation_id: FederationId,
+    timestamp: String,
+    operation: &ListedOperation,
+) -> LedgerEntry {
+    let entry = &operation.entry;
+    let (mut amount, fee) = operation_amounts(entry);
+
+    let (direction, kind, counterparty) = match entry.operation_module_kind() {
+        "ln" => match entry.meta::<LightningOperationMeta>().variant {
+            LightningOperationMetaVariant::Pay(pay) => (
+                Some(Direction::Out),
+                "ln_pay",
+                Some(pay.invoice.get_payee_pub_key().to_string()),
+            ),
+            LightningOperationMetaVariant::Receive { .. } => {
+                (Some(Direction::In), "ln_receive", None)
+            }
+            LightningOperationMetaVariant::Claim { .. } => (Some(Direction::In), "ln_claim", None),
+        },
+        "mint" => match entry.meta::<MintOperationMeta>().variant {
+            MintOperationMetaVariant::Reissuance { .. } => {
+                (Some(Direction::In), "mint_reissue", None)
+            }
+            MintOperationMetaVariant::SpendOOB { .. } => (Some(Direction::Out), "mint_spend", None),
+        },
+        "wallet" => match entry.meta::<WalletOperationMeta>().variant {
+            WalletOperationMetaVariant::Deposit { address, .. } => {
+                amount = deposited_amount(operation);
+                (
+                    Some(Direction::In),
+                    "wallet_deposit",
+                    Some(address.assume_checked().to_string()),
+                )
+            }
+            WalletOperationMetaVariant::Withdraw { address, .. } => (
+                Some(Direction::Out),
+                "wallet_withdraw",
+                Some(address.assume_checked().to_string()),
+            ),
+            _ => (None, "wallet_other", None),
+        },
+        _ => (None, entry.operation_module_kind(), None),
+    };
+
+    LedgerEntry {
+        timestamp,
+        federation_id,
+        direction,
+        kind: kind.to_owned(),
+        amount_msat: amount.map(|amount| amount.msats),
+        fee_msat: fee.map(|fee| fee.msats),
+        counterparty,
+        operation_id: operation.key.operation_id,
+        status: operation.status(),
+    }
+}
+
+/// Walks the operation logs of `clients` and renders them as a ledger in the
+/// requested format, newest first.
+pub async fn export_transactions(
+    clients: Vec<ClientHandleArc>,
+    req: &ExportTransactionsRequest,
+) -> anyhow::Result<String> {
+    let filter = OperationFilter {
+        start_time: req.start_time.map(unix_to_system_time),
+        end_time: req.end_time.map(unix_to_system_time),
+        ..Default::default()
+    };
+
+    let mut operations = Vec::new();
+    for client in &clients {
+        let federation_id = client.federation_id();
+        operations.extend(
+            list_filtered(client, &filter, usize::MAX, None)
+                .await
+                .into_iter()
+                .map(|operation| (federation_id, operation)),
+        );
+    }
+    operations.sort_by_key(|(_, operation)| std::cmp::Reverse(operation.key.creation_time));
+
+    let mut lines = Vec::with_capacity(operations.len() + 1);
+    if req.format == ExportFormat::Csv {
+        lines.push(CSV_HEADER.to_owned());
+    }
+    for (federation_id, operation) in operations {
+        let timestamp = format_creation_time(operation.key.creation_time)?;
+        let ledger_entry = ledger_entry(federation_id, timestamp, &operation);
+        lines.push(match req.format {
+            ExportFormat::Csv => ledger_entry.to_csv_row(),
+            ExportFormat::Jsonl => serde_json::to_string(&ledger_entry)?,
+        });
+    }
+
+    let mut export = lines.join("\n");
+    export.push('\n');
+    Ok(export)
+}
+
+async fn get_clients(
+    state: &AppState,
+    req: &ExportTransactionsRequest,
+) -> Result<Vec<ClientHandleArc>, AppError> {
+    if req.all_federations {
+        Ok(state.multimint.all().await)
+    } else {
+        Ok(vec![state.get_client(req.federation_id).await?])
+    }
+}
+
+pub async fn handle_ws(state: AppState, v: Value) -> Result<Value, AppError> {
+    let v = serde_json::from_value::<ExportTransactionsRequest>(v)
+        .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, anyhow!("Invalid request: {}", e)))?;
+    let clients = get_clients(&state, &v).await?;
+    let export = export_transactions(clients, &v).await?;
+    Ok(json!(export))
+}
+
+#[axum_macros::debug_handler]
+pub async fn handle_rest(
+    State(state): State<AppState>,
+    Json(req): Json<ExportTransactionsRequest>,
+) -> Result<Response, AppError> {
+    let clients = get_clients(&state, &req).await?;
+    let export = export_transactions(clients, &req).await?;
+    let content_type = match req.format {
+        ExportFormat::Csv => "text/csv",
+        ExportFormat::Jsonl => "application/x-ndjson",
+    };
+    Ok(([(header::CONTENT_TYPE, content_type)], export).into_response())
+}
@@ -33,3 +33,127 @@ pub async fn handle_rest(
     let federation_ids = _federation_ids(state.multimint).await?;
     Ok(Json(federation_ids))
 }
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeMap;
+
+    use multimint::db::{FederationConfig, FederationIdKey};
+    use multimint::fedimint_api_client::api::ApiVersionSet;
+    use multimint::fedimint_client::db::{
+        CachedApiVersionSet, CachedApiVersionSetKey, ClientConfigKey, ClientInitStateKey,
+        InitModeComplete, InitState,
+    };
+    use multimint::fedimint_client::Client;
+    use multimint::fedimint_core::config::{ClientConfig, ClientModuleConfig, GlobalClientConfig};
+    use multimint::fedimint_core::db::mem_impl::MemDatabase;
+    use multimint::fedimint_core::db::{Database, IDatabaseTransactionOpsCoreTyped};
+    use multimint::fedimint_core::encoding::Encodable;
+    use multimint::fedimint_core::invite_code::InviteCode;
+    use multimint::fedimint_core::module::{ApiVersion, CoreConsensusVersion};
+    use multimint::fedimint_core::util::SafeUrl;
+    use multimint::fedimint_core::PeerId;
+    use multimint::fedimint_mint_client::config::MintClientConfig;
+
+    use super::*;
+
+    /// Stores a federation with only a mint module as already joined, with
+    /// its api versions cached, so the multimint opens its client without
+    /// reaching its guardian.
+    async fn join_offline_federation(db: &Database) -> FederationId {
+        let mint_instance_id = 0;
+        let config = ClientConfig {
+            global: GlobalClientConfig {
+                api_endpoints: BTreeMap::new(),
+                broadcast_public_keys: None,
+                consensus_version: CoreConsensusVersion::new(2, 0),
+                meta: BTreeMap::new(),
+            },
+            modules: BTreeMap::from([(
+                mint_instance_id,
+                ClientModuleConfig::from_typed(
+                    mint_instance_id,
+                    multimint::fedimint_mint_client::KIND,
+                    multimint::fedimint_mint_client::MODULE_CONSENSUS_VERSION,
+                    MintClientConfig {
+                        tbs_pks: Default::default(),
+                        fee_consensus: Default::default(),
+                        peer_tbs_pks: BTreeMap::new(),
+                        max_notes_per_denomination: 3,
+                    },
+                )
+                .unwrap(),
+            )]),
+        };
+        let federation_id = config.calculate_federation_id();
+
+        // The client database is created first so it's at the current version
+        // rather than migrated
+        let client_db = db.with_prefix(federation_id.consensus_encode_to_vec());
+        Client::builder(client_db.clone()).await.unwrap();
+        let mut dbtx = client_db.begin_transaction().await;
+        dbtx.insert_entry(&ClientConfigKey, &config).await;
+        dbtx.insert_entry(
+            &ClientInitStateKey,
+            &InitState::Complete(InitModeComplete::Fresh),
+        )
+        .await;
+        dbtx.insert_entry(
+            &CachedApiVersionSetKey,
+            &CachedApiVersionSet(ApiVersionSet {
+                core: ApiVersion::new(0, 0),
+                modules: BTreeMap::from([(mint_instance_id, ApiVersion::new(0, 0))]),
+            }),
+        )
+        .await;
+        dbtx.commit_tx().await;
+
+        let url = SafeUrl::parse("ws://127.0.0.1:1/").unwrap();
+        let mut dbtx = db.begin_transaction().await;
+        dbtx.insert_entry(
+            &FederationIdKey { id: federation_id },
+            &FederationConfig {
+                invite_code: InviteCode::new(url, PeerId::from(0), federation_id, None),
+            },
+        )
+        .await;
+        dbtx.commit_tx().await;
+
+        federation_id
+    }
+
+    async fn state_with_database(db: Database) -> AppState {
+        let multimint = MultiMint::builder()
+            .without_env_mnemonic()
+            .build_with_database(db)
+            .await
+            .unwrap();
+        assert!(multimint.wait_for_loading().await.is_empty());
+        AppState::with_multimint(multimint, Default::default(), Default::default())
+    }
+
+    #[tokio::test]
+    async fn lists_no_federations_before_joining() {
+        let state =
+            state_with_database(Database::new(MemDatabase::new(), Default::default())).await;
+
+        let Json(response) = handle_rest(State(state.clone())).await.unwrap();
+        assert!(response.federation_ids.is_empty());
+
+        let ws_response = handle_ws(state, Value::Null).await.unwrap();
+        assert_eq!(ws_response, json!({ "federationIds": [] }));
+    }
+
+    #[tokio::test]
+    async fn lists_joined_federations() {
+        let db = Database::new(MemDatabase::new(), Default::default());
+        let federation_id = join_offline_federation(&db).await;
+        let state = state_with_database(db).await;
+
+        let Json(response) = handle_rest(State(state.clone())).await.unwrap();
+        assert_eq!(response.federation_ids, vec![federation_id]);
+
+        let ws_response = handle_ws(state, Value::Null).await.unwrap();
+        assert_eq!(ws_response, json!({ "federationIds": [federation_id] }));
+    }
+}
@@ -0,0 +1,186 @@
+use std::collections::BTreeMap;
+use std::time::{Duration, Instant};
+
+use axum::extract::State;
+use axum::Json;
+use multimint::fedimint_api_client::api::{FederationApiExt, IGlobalFederationApi};
+use multimint::fedimint_client::ClientHandleArc;
+use multimint::fedimint_core::config::{FederationId, PeerUrl};
+use multimint::fedimint_core::endpoint_constants::{SESSION_COUNT_ENDPOINT, VERSION_ENDPOINT};
+use multimint::fedimint_core::module::ApiRequestErased;
+use multimint::fedimint_core::PeerId;
+use multimint::fedimint_wallet_client::api::WalletFederationApi;
+use serde::Serialize;
+use serde_json::{json, Value};
+use tracing::debug;
+
+use crate::error::AppError;
+use crate::state::AppState;
+
+/// How long to wait for a guardian to answer before considering it
+/// unreachable
+const PEER_TIMEOUT: Duration = Duration::from_secs(5);
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct FederationStatus {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub federation_name: Option<String>,
+    pub total_guardians: usize,
+    pub online_guardians: usize,
+    /// Number of guardians needed to reach consensus
+    pub threshold: usize,
+    /// Some guardians are unreachable, but the federation can still reach
+    /// consensus
+    pub degraded: bool,
+    /// Fewer than `threshold` guardians are reachable, payments will fail
+    pub offline: bool,
+    /// Bitcoin block count the guardians agreed on, absent without a wallet
+    /// module or if they didn't agree in time
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub block_count: Option<u64>,
+    pub guardians: Vec<GuardianStatus>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GuardianStatus {
+    pub peer_id: PeerId,
+    pub name: String,
+    pub url: String,
+    pub reachable: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub latency_ms: Option<u64>,
+    /// Number of consensus sessions the guardian has completed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub session_count: Option<u64>,
+    /// Api versions the guardian supports
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub api_version: Option<Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+async fn request_peer(
+    api: &dyn IGlobalFederationApi,
+    peer_id: PeerId,
+    method: &str,
+) -> anyhow::Result<Value> {
+    api.request_single_peer(
+        Some(PEER_TIMEOUT),
+        method.to_owned(),
+        ApiRequestErased::default(),
+        peer_id,
+    )
+    .await
+    .map_err(|e| anyhow::anyhow!("{e}"))
+}
+
+async fn guardian_status(
+    api: &dyn IGlobalFederationApi,
+    peer_id: PeerId,
+    peer_url: &PeerUrl,
+) -> GuardianStatus {
+    let start = Instant::now();
+    let session_count = request_peer(api, peer_id, SESSION_COUNT_ENDPOINT).await;
+    let latency_ms = start.elapsed().as_millis() as u64;
+
+    let mut status = GuardianStatus {
+        peer_id,
+        name: peer_url.name.clone(),
+        url: peer_url.url.to_string(),
+        reachable: false,
+        latency_ms: None,
+        session_count: None,
+        api_version: None,
+        error: None,
+    };
+
+    match session_count {
+        Ok(session_count) => {
+            status.reachable = true;
+            status.latency_ms = Some(latency_ms);
+            status.session_count = session_count.as_u64();
+            match request_peer(api, peer_id, VERSION_ENDPOINT).await {
+                Ok(api_version) => status.api_version = Some(api_version),
+                Err(e) => status.error = Some(e.to_string()),
+            }
+        }
+        Err(e) => status.error = Some(e.to_string()),
+    }
+
+    status
+}
+
+/// Block count of the wallet module's consensus, which needs a threshold of
+/// guardians to agree.
+async fn consensus_block_count(client: &ClientHandleArc) -> Option<u64> {
+    let instance_id = client.get_first_instance(&multimint::fedimint_wallet_client::KIND)?;
+    let api = client.api().with_module(instance_id);
+    match tokio::time::timeout(PEER_TIMEOUT, api.fetch_consensus_block_count()).await {
+        Ok(Ok(block_count)) => Some(block_count),
+        Ok(Err(e)) => {
+            debug!("Couldn't get the consensus block count: {e}");
+            None
+        }
+        Err(_) => None,
+    }
+}
+
+pub(crate) async fn federation_status(client: &ClientHandleArc) -> FederationStatus {
+    let config = client.config().await;
+    let api = client.api();
+
+    let (guardians, block_count) = futures_util::future::join(
+        futures_util::future::join_all(
+            config
+                .global
+                .api_endpoints
+                .iter()
+                .map(|(peer_id, peer_url)| guardian_status(api, *peer_id, peer_url)),
+        ),
+        consensus_block_count(client),
+    )
+    .await;
+
+    let total_guardians = guardians.len();
+    let online_guardians = guardians.iter().filter(|g| g.reachable).count();
+    let threshold = total_guardians - total_guardians.saturating_sub(1) / 3;
+
+    FederationStatus {
+        federation_name: config.global.meta.get("federation_name").cloned(),
+        total_guardians,
+        online_guardians,
+        threshold,
+        degraded: online_guardians < total_guardians,
+        offline: online_guardians < threshold,
+        block_count,
+        guardians,
+    }
+}
+
+async fn _federation_status(
+    state: &AppState,
+) -> Result<BTreeMap<FederationId, FederationStatus>, AppError> {
+    let clients = state.multimint.all().await;
+    let statuses = futures_util::future::join_all(clients.iter().map(federation_status)).await;
+    Ok(clients
+        .iter()
+        .map(|client| client.federation_id())
+        .zip(statuses)
+        .collect())
+}
+
+pub async fn handle_ws(state: AppState) -> Result<Value, AppError> {
+    let status = _federation_status(&state).await?;
+    let status_json = json!(status);
+    Ok(status_json)
+}
+
+#[axum_macros::debug_handler]
+pub async fn handle_rest(
+    State(state): State<AppState>,
+) -> Result<Json<BTreeMap<FederationId, FederationStatus>>, AppError> {
+    let status = _federation_status(&state).await?;
+    Ok(Json(status))
+}
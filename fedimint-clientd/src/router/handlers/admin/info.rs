@@ -1,12 +1,9 @@
-use std::collections::{BTreeMap, HashMap};
+use std::collections::HashMap;
 
-use anyhow::Error;
 use axum::extract::State;
 use axum::Json;
 use multimint::fedimint_core::config::FederationId;
-use multimint::fedimint_core::{Amount, TieredCounts};
-use multimint::fedimint_mint_client::MintClientModule;
-use multimint::fedimint_wallet_client::WalletClientModule;
+use multimint::types::InfoResponse;
 use multimint::MultiMint;
 use serde::Serialize;
 use serde_json::{json, Value};
@@ -14,45 +11,29 @@ use serde_json::{json, Value};
 use crate::error::AppError;
 use crate::state::AppState;
 
+/// Info of a federation, or why it couldn't be read
 #[derive(Debug, Serialize)]
-#[serde(rename_all = "camelCase")]
-pub struct InfoResponse {
-    pub network: String,
-    pub meta: BTreeMap<String, String>,
-    pub total_amount_msat: Amount,
-    pub total_num_notes: usize,
-    pub denominations_msat: TieredCounts,
+#[serde(untagged)]
+pub enum FederationInfo {
+    Info(Box<InfoResponse>),
+    Error { error: String },
 }
 
-async fn _info(multimint: MultiMint) -> Result<HashMap<FederationId, InfoResponse>, Error> {
-    let mut info = HashMap::new();
-
-    for (id, client) in multimint.clients.lock().await.iter() {
-        let mint_client = client.get_first_module::<MintClientModule>();
-        let wallet_client = client.get_first_module::<WalletClientModule>();
-        let summary = mint_client
-            .get_wallet_summary(
-                &mut client
-                    .db()
-                    .begin_transaction_nc()
-                    .await
-                    .to_ref_with_prefix_module_id(1),
-            )
-            .await;
-
-        info.insert(
-            *id,
-            InfoResponse {
-                network: wallet_client.get_network().to_string(),
-                meta: client.config().await.global.meta.clone(),
-                total_amount_msat: summary.total_amount(),
-                total_num_notes: summary.count_items(),
-                denominations_msat: summary,
-            },
-        );
-    }
-
-    Ok(info)
+async fn _info(multimint: MultiMint) -> Result<HashMap<FederationId, FederationInfo>, AppError> {
+    Ok(multimint
+        .info()
+        .await
+        .into_iter()
+        .map(|(id, info)| {
+            let info = match info {
+                Ok(info) => FederationInfo::Info(Box::new(info)),
+                Err(e) => FederationInfo::Error {
+                    error: e.to_string(),
+                },
+            };
+            (id, info)
+        })
+        .collect())
 }
 
 pub async fn handle_ws(state: AppState, _v: Value) -> Result<Value, AppError> {
@@ -64,7 +45,7 @@ pub async fn handle_ws(state: AppState, _v: Value) -> Result<Value, AppError> {
 #[axum_macros::debug_handler]
 pub async fn handle_rest(
     State(state): State<AppState>,
-) -> Result<Json<HashMap<FederationId, InfoResponse>>, AppError> {
+) -> Result<Json<HashMap<FederationId, FederationInfo>>, AppError> {
     let info = _info(state.multimint).await?;
     Ok(Json(info))
 }
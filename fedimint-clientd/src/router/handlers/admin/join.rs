@@ -15,6 +15,11 @@ use crate::state::AppState;
 #[serde(rename_all = "camelCase")]
 pub struct JoinRequest {
     pub invite_code: InviteCode,
+    /// Recover the funds of a previous client with the same secret from the
+    /// federation's history. Also happens without this flag when the
+    /// federation holds a backup for the secret.
+    #[serde(default)]
+    pub recover: bool,
 }
 
 #[derive(Debug, Serialize)]
@@ -22,16 +27,22 @@ pub struct JoinRequest {
 pub struct JoinResponse {
     pub this_federation_id: FederationId,
     pub federation_ids: Vec<FederationId>,
+    /// The client is recovering, see `/v2/admin/recovery-status` for its
+    /// progress
+    pub recovering: bool,
 }
 
 async fn _join(mut multimint: MultiMint, req: JoinRequest) -> Result<JoinResponse, Error> {
-    let this_federation_id = multimint.register_new(req.invite_code.clone()).await?;
+    let (this_federation_id, recovering) = multimint
+        .register_new_with_recovery(req.invite_code.clone(), req.recover)
+        .await?;
 
     let federation_ids = multimint.ids().await.into_iter().collect::<Vec<_>>();
 
     Ok(JoinResponse {
         this_federation_id,
         federation_ids,
+        recovering,
     })
 }
 
@@ -1,16 +1,26 @@
-use std::time::UNIX_EPOCH;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use anyhow::anyhow;
 use axum::extract::State;
 use axum::http::StatusCode;
 use axum::Json;
+use futures_util::future::join_all;
+use multimint::fedimint_client::db::ChronologicalOperationLogKey;
+use multimint::fedimint_client::oplog::OperationLogEntry;
 use multimint::fedimint_client::ClientHandleArc;
 use multimint::fedimint_core::config::FederationId;
 use multimint::fedimint_core::core::OperationId;
+use multimint::fedimint_core::encoding::{Decodable, Encodable};
+use multimint::fedimint_core::module::registry::ModuleDecoderRegistry;
+use multimint::operations::PAGE_SIZE;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use time::format_description::well_known::iso8601;
 use time::OffsetDateTime;
+use tracing::debug;
 
+use super::operation::current_state;
+use super::OperationStatus;
 use crate::error::AppError;
 use crate::state::AppState;
 
@@ -18,63 +28,279 @@ use crate::state::AppState;
 #[serde(rename_all = "camelCase")]
 pub struct ListOperationsRequest {
     pub limit: usize,
-    pub federation_id: FederationId,
+    pub federation_id: Option<FederationId>,
+    /// Merge the histories of all federations, ordered by creation time
+    #[serde(default)]
+    pub all_federations: bool,
+    /// `nextCursor` of the previous page
+    pub cursor: Option<String>,
+    /// Only include operations of these module kinds, e.g. `ln`, `mint`,
+    /// `wallet`
+    pub kinds: Option<Vec<String>>,
+    /// Only include operations created at or after this unix timestamp
+    pub start_time: Option<u64>,
+    /// Only include operations created before this unix timestamp
+    pub end_time: Option<u64>,
+    /// Only include operations in one of these states
+    pub statuses: Option<Vec<OperationStatus>>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ListOperationsResponse {
+    pub operations: Vec<OperationOutput>,
+    /// Pass as `cursor` to fetch the next page, absent on the last page
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_cursor: Option<String>,
 }
 
-#[derive(Serialize)]
+#[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct OperationOutput {
     pub id: OperationId,
+    pub federation_id: FederationId,
     pub creation_time: String,
     pub operation_kind: String,
     pub operation_meta: serde_json::Value,
+    pub status: OperationStatus,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub outcome: Option<serde_json::Value>,
 }
 
-async fn _list_operations(
-    client: ClientHandleArc,
-    req: ListOperationsRequest,
-) -> Result<Value, AppError> {
+#[derive(Debug, Default)]
+pub(crate) struct OperationFilter {
+    pub kinds: Option<Vec<String>>,
+    pub start_time: Option<SystemTime>,
+    pub end_time: Option<SystemTime>,
+    pub statuses: Option<Vec<OperationStatus>>,
+}
+
+pub(crate) fn unix_to_system_time(secs: u64) -> SystemTime {
+    UNIX_EPOCH + Duration::from_secs(secs)
+}
+
+impl OperationFilter {
+    fn new(req: &ListOperationsRequest) -> Self {
+        Self {
+            kinds: req.kinds.clone(),
+            start_time: req.start_time.map(unix_to_system_time),
+            end_time: req.end_time.map(unix_to_system_time),
+            statuses: req.statuses.clone(),
+        }
+    }
+
+    /// Operations are listed newest first, so once one is older than the
+    /// range all the following ones are too.
+    fn is_past_range(&self, key: &ChronologicalOperationLogKey) -> bool {
+        self.start_time
+            .is_some_and(|start_time| key.creation_time < start_time)
+    }
+
+    /// Whether the operation matches the filter, apart from its status which
+    /// may need to be read from its state machine.
+    fn matches(&self, key: &ChronologicalOperationLogKey, entry: &OperationLogEntry) -> bool {
+        if self
+            .end_time
+            .is_some_and(|end_time| key.creation_time >= end_time)
+        {
+            return false;
+        }
+        if let Some(kinds) = &self.kinds {
+            if !kinds
+                .iter()
+                .any(|kind| kind == entry.operation_module_kind())
+            {
+                return false;
+            }
+        }
+        true
+    }
+
+    fn matches_status(&self, status: OperationStatus) -> bool {
+        match &self.statuses {
+            Some(statuses) => statuses.contains(&status),
+            None => true,
+        }
+    }
+}
+
+/// An operation of the operation log together with its current state.
+#[derive(Debug)]
+pub(crate) struct ListedOperation {
+    pub key: ChronologicalOperationLogKey,
+    pub entry: OperationLogEntry,
+    /// Latest state of the operation's state machine, only read for
+    /// operations without an outcome, e.g. because they were never awaited
+    pub state: Option<Value>,
+}
+
+impl ListedOperation {
+    /// Reads the state of operations that have no outcome yet, which also
+    /// stores the outcome of the ones that finished in the meantime.
+    async fn new(
+        client: &ClientHandleArc,
+        key: ChronologicalOperationLogKey,
+        entry: OperationLogEntry,
+    ) -> Self {
+        let state = if entry.outcome::<Value>().is_none() {
+            current_state(client, key.operation_id, &entry)
+                .await
+                .unwrap_or_else(|e| {
+                    debug!(operation_id = ?key.operation_id, "Couldn't read operation state: {e}");
+                    None
+                })
+        } else {
+            None
+        };
+        ListedOperation { key, entry, state }
+    }
+
+    pub fn outcome(&self) -> Option<Value> {
+        self.entry.outcome::<Value>()
+    }
+
+    pub fn status(&self) -> OperationStatus {
+        OperationStatus::from_state(&self.entry, self.outcome().as_ref().or(self.state.as_ref()))
+    }
+}
+
+/// Walks the operation log of `client` newest first, starting after
+/// `cursor`, until `limit` operations matching `filter` are found or the log
+/// is exhausted.
+///
+/// The states of the matching operations of a page are read concurrently, so
+/// a page with operations that are still in progress takes about
+/// `UPDATE_TIMEOUT` longer.
+pub(crate) async fn list_filtered(
+    client: &ClientHandleArc,
+    filter: &OperationFilter,
+    limit: usize,
+    mut cursor: Option<ChronologicalOperationLogKey>,
+) -> Vec<ListedOperation> {
+    let mut operations = Vec::new();
+    if limit == 0 {
+        return operations;
+    }
+
+    loop {
+        let page = client
+            .operation_log()
+            .list_operations(PAGE_SIZE, cursor)
+            .await;
+        let exhausted = page.len() < PAGE_SIZE;
+        cursor = page.last().map(|(key, _)| *key);
+
+        let past_range = page.iter().any(|(key, _)| filter.is_past_range(key));
+        let candidates = page
+            .into_iter()
+            .take_while(|(key, _)| !filter.is_past_range(key))
+            .filter(|(key, entry)| filter.matches(key, entry))
+            .map(|(key, entry)| ListedOperation::new(client, key, entry));
+
+        for operation in join_all(candidates).await {
+            if filter.matches_status(operation.status()) {
+                operations.push(operation);
+                if operations.len() == limit {
+                    return operations;
+                }
+            }
+        }
+
+        if exhausted || past_range {
+            return operations;
+        }
+    }
+}
+
+fn decode_cursor(cursor: &str) -> Result<ChronologicalOperationLogKey, AppError> {
+    ChronologicalOperationLogKey::consensus_decode_hex(cursor, &ModuleDecoderRegistry::default())
+        .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, anyhow!("Invalid cursor: {e}")))
+}
+
+pub(crate) fn format_creation_time(creation_time: SystemTime) -> Result<String, anyhow::Error> {
     const ISO8601_CONFIG: iso8601::EncodedConfig = iso8601::Config::DEFAULT
         .set_formatted_components(iso8601::FormattedComponents::DateTime)
         .encode();
-    let operations = client
-        .operation_log()
-        .list_operations(req.limit, None)
-        .await
-        .into_iter()
-        .map(|(k, v)| -> Result<OperationOutput, anyhow::Error> {
-            let creation_time = OffsetDateTime::from_unix_timestamp(
-                k.creation_time
-                    .duration_since(UNIX_EPOCH)
-                    .map_err(|e| {
-                        anyhow::anyhow!("Couldn't convert time from SystemTime to timestamp: {}", e)
-                    })?
-                    .as_secs() as i64,
-            )
+    OffsetDateTime::from_unix_timestamp(
+        creation_time
+            .duration_since(UNIX_EPOCH)
             .map_err(|e| {
-                anyhow::anyhow!(
-                    "Couldn't convert time from SystemTime to OffsetDateTime: {}",
-                    e
-                )
+                anyhow::anyhow!("Couldn't convert time from SystemTime to timestamp: {}", e)
             })?
-            .format(&iso8601::Iso8601::<ISO8601_CONFIG>)
-            .map_err(|e| anyhow::anyhow!("Couldn't format OffsetDateTime as ISO8601: {}", e))?;
-
-            Ok(OperationOutput {
-                id: k.operation_id,
-                creation_time,
-                operation_kind: v.operation_module_kind().to_owned(),
-                operation_meta: v.meta(),
-                outcome: v.outcome(),
-            })
-        })
+            .as_secs() as i64,
+    )
+    .map_err(|e| {
+        anyhow::anyhow!(
+            "Couldn't convert time from SystemTime to OffsetDateTime: {}",
+            e
+        )
+    })?
+    .format(&iso8601::Iso8601::<ISO8601_CONFIG>)
+    .map_err(|e| anyhow::anyhow!("Couldn't format OffsetDateTime as ISO8601: {}", e))
+}
+
+async fn _list_operations(
+    clients: Vec<ClientHandleArc>,
+    req: ListOperationsRequest,
+) -> Result<ListOperationsResponse, AppError> {
+    let filter = OperationFilter::new(&req);
+    let cursor = req.cursor.as_deref().map(decode_cursor).transpose()?;
+
+    let mut operations = Vec::new();
+    for client in &clients {
+        let federation_id = client.federation_id();
+        operations.extend(
+            list_filtered(client, &filter, req.limit, cursor)
+                .await
+                .into_iter()
+                .map(|operation| (federation_id, operation)),
+        );
+    }
+
+    // Merge the histories newest first, each federation's page is already
+    // ordered so only the first `limit` operations overall are kept
+    operations.sort_by_key(|(_, operation)| std::cmp::Reverse(operation.key.creation_time));
+    operations.truncate(req.limit);
+
+    let next_cursor = match operations.last() {
+        Some((_, operation)) if operations.len() == req.limit => {
+            Some(operation.key.consensus_encode_to_hex())
+        }
+        _ => None,
+    };
+
+    let operations = operations
+        .into_iter()
+        .map(
+            |(federation_id, operation)| -> Result<OperationOutput, anyhow::Error> {
+                Ok(OperationOutput {
+                    id: operation.key.operation_id,
+                    federation_id,
+                    creation_time: format_creation_time(operation.key.creation_time)?,
+                    operation_kind: operation.entry.operation_module_kind().to_owned(),
+                    operation_meta: operation.entry.meta(),
+                    status: operation.status(),
+                    outcome: operation.outcome(),
+                })
+            },
+        )
         .collect::<Result<Vec<_>, anyhow::Error>>()?;
 
-    Ok(json!({
-        "operations": operations,
-    }))
+    Ok(ListOperationsResponse {
+        operations,
+        next_cursor,
+    })
+}
+
+async fn get_clients(
+    state: &AppState,
+    req: &ListOperationsRequest,
+) -> Result<Vec<ClientHandleArc>, AppError> {
+    if req.all_federations {
+        Ok(state.multimint.all().await)
+    } else {
+        Ok(vec![state.get_client(req.federation_id).await?])
+    }
 }
 
 pub async fn handle_ws(state: AppState, v: Value) -> Result<Value, AppError> {
@@ -84,8 +310,8 @@ pub async fn handle_ws(state: AppState, v: Value) -> Result<Value, AppError> {
             anyhow::anyhow!("Invalid request: {}", e),
         )
     })?;
-    let client = state.get_client(v.federation_id).await?;
-    let operations = _list_operations(client, v).await?;
+    let clients = get_clients(&state, &v).await?;
+    let operations = _list_operations(clients, v).await?;
     let operations_json = json!(operations);
     Ok(operations_json)
 }
@@ -94,8 +320,8 @@ pub async fn handle_ws(state: AppState, v: Value) -> Result<Value, AppError> {
 pub async fn handle_rest(
     State(state): State<AppState>,
     Json(req): Json<ListOperationsRequest>,
-) -> Result<Json<Value>, AppError> {
-    let client = state.get_client(req.federation_id).await?;
-    let operations = _list_operations(client, req).await?;
+) -> Result<Json<ListOperationsResponse>, AppError> {
+    let clients = get_clients(&state, &req).await?;
+    let operations = _list_operations(clients, req).await?;
     Ok(Json(operations))
 }
@@ -0,0 +1,42 @@
+use anyhow::anyhow;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use multimint::fedimint_core::config::FederationId;
+use serde::Deserialize;
+use serde_json::{json, Value};
+
+use crate::error::AppError;
+use crate::meta::FederationMeta;
+use crate::state::AppState;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MetaRequest {
+    pub federation_id: Option<FederationId>,
+    /// Fetch the meta again instead of returning the cached one
+    #[serde(default)]
+    pub refresh: bool,
+}
+
+async fn _meta(state: &AppState, req: MetaRequest) -> Result<FederationMeta, AppError> {
+    let client = state.get_client(req.federation_id).await?;
+    Ok(state.meta_cache.get(&client, req.refresh).await)
+}
+
+pub async fn handle_ws(state: AppState, v: Value) -> Result<Value, AppError> {
+    let v = serde_json::from_value::<MetaRequest>(v)
+        .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, anyhow!("Invalid request: {}", e)))?;
+    let meta = _meta(&state, v).await?;
+    let meta_json = json!(meta);
+    Ok(meta_json)
+}
+
+#[axum_macros::debug_handler]
+pub async fn handle_rest(
+    State(state): State<AppState>,
+    Json(req): Json<MetaRequest>,
+) -> Result<Json<FederationMeta>, AppError> {
+    let meta = _meta(&state, req).await?;
+    Ok(Json(meta))
+}
@@ -1,35 +1,170 @@
 pub mod backup;
 pub mod config;
 pub mod discover_version;
+pub mod export_transactions;
 pub mod federation_ids;
+pub mod federation_status;
 pub mod info;
 pub mod join;
 pub mod list_operations;
+pub mod meta;
 pub mod module;
+pub mod operation;
+pub mod preview;
+pub mod recovery_status;
 pub mod restore;
 
-use info::InfoResponse;
-use multimint::fedimint_client::ClientHandleArc;
-use multimint::fedimint_mint_client::MintClientModule;
-use multimint::fedimint_wallet_client::WalletClientModule;
+use multimint::fedimint_client::oplog::OperationLogEntry;
+use multimint::fedimint_ln_client::{
+    InternalPayState, LightningOperationMeta, LightningOperationMetaVariant, LnPayState,
+    LnReceiveState,
+};
+use multimint::fedimint_mint_client::{
+    MintOperationMeta, MintOperationMetaVariant, ReissueExternalNotesState, SpendOOBState,
+};
+use multimint::fedimint_wallet_client::{
+    DepositStateV1, DepositStateV2, WalletOperationMeta, WalletOperationMetaVariant, WithdrawState,
+};
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
-pub async fn _get_note_summary(client: &ClientHandleArc) -> anyhow::Result<InfoResponse> {
-    let mint_client = client.get_first_module::<MintClientModule>();
-    let wallet_client = client.get_first_module::<WalletClientModule>();
-    let summary = mint_client
-        .get_wallet_summary(
-            &mut client
-                .db()
-                .begin_transaction_nc()
-                .await
-                .to_ref_with_prefix_module_id(1),
-        )
-        .await;
-    Ok(InfoResponse {
-        network: wallet_client.get_network().to_string(),
-        meta: client.config().await.global.meta.clone(),
-        total_amount_msat: summary.total_amount(),
-        total_num_notes: summary.count_items(),
-        denominations_msat: summary,
-    })
+/// Status of an operation normalized across the ln, mint and wallet modules.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum OperationStatus {
+    Pending,
+    Succeeded,
+    /// The operation ended without the expected result and its funds didn't
+    /// return to the wallet, or it's unknown whether they did
+    Failed,
+    /// The funds returned to the wallet, e.g. a payment whose funding was
+    /// rejected or that was refunded by the gateway
+    Refunded,
+}
+
+impl OperationStatus {
+    /// Classifies an operation by `state`, its outcome or else the latest
+    /// update of its state machine, decoded as the state type of the
+    /// operation's module. Operations without a state or of other modules are
+    /// still pending.
+    pub fn from_state(entry: &OperationLogEntry, state: Option<&Value>) -> Self {
+        state
+            .and_then(|state| Self::classify(entry, state))
+            .unwrap_or(OperationStatus::Pending)
+    }
+
+    fn classify(entry: &OperationLogEntry, state: &Value) -> Option<Self> {
+        use OperationStatus::{Failed, Pending, Refunded, Succeeded};
+
+        let status = match entry.operation_module_kind() {
+            "ln" => match entry.meta::<LightningOperationMeta>().variant {
+                LightningOperationMetaVariant::Pay(pay) if pay.is_internal_payment => {
+                    match decode::<InternalPayState>(state)? {
+                        InternalPayState::Funding => Pending,
+                        InternalPayState::Preimage(_) => Succeeded,
+                        InternalPayState::RefundSuccess { .. }
+                        | InternalPayState::FundingFailed { .. } => Refunded,
+                        InternalPayState::RefundError { .. }
+                        | InternalPayState::UnexpectedError(_) => Failed,
+                    }
+                }
+                LightningOperationMetaVariant::Pay(_) => match decode::<LnPayState>(state)? {
+                    LnPayState::Created
+                    | LnPayState::Funded { .. }
+                    | LnPayState::WaitingForRefund { .. }
+                    | LnPayState::AwaitingChange => Pending,
+                    LnPayState::Success { .. } => Succeeded,
+                    LnPayState::Canceled | LnPayState::Refunded { .. } => Refunded,
+                    LnPayState::UnexpectedError { .. } => Failed,
+                },
+                LightningOperationMetaVariant::Receive { .. }
+                | LightningOperationMetaVariant::Claim { .. } => {
+                    match decode::<LnReceiveState>(state)? {
+                        LnReceiveState::Created
+                        | LnReceiveState::WaitingForPayment { .. }
+                        | LnReceiveState::Funded
+                        | LnReceiveState::AwaitingFunds => Pending,
+                        LnReceiveState::Claimed => Succeeded,
+                        LnReceiveState::Canceled { .. } => Failed,
+                    }
+                }
+            },
+            #[cfg(feature = "lnv2")]
+            "lnv2" => {
+                use multimint::fedimint_lnv2_client as lnv2;
+
+                match entry.meta::<lnv2::LightningOperationMeta>() {
+                    lnv2::LightningOperationMeta::Send(_) => match decode(state)? {
+                        lnv2::SendState::Funding
+                        | lnv2::SendState::Funded
+                        | lnv2::SendState::Refunding => Pending,
+                        lnv2::SendState::Success => Succeeded,
+                        lnv2::SendState::Refunded => Refunded,
+                        // Also reported when the funding transaction was
+                        // rejected, so it's unknown whether funds left
+                        lnv2::SendState::Failure => Failed,
+                    },
+                    lnv2::LightningOperationMeta::Receive(_) => match decode(state)? {
+                        lnv2::ReceiveState::Pending | lnv2::ReceiveState::Claiming => Pending,
+                        lnv2::ReceiveState::Claimed => Succeeded,
+                        lnv2::ReceiveState::Expired | lnv2::ReceiveState::Failure => Failed,
+                    },
+                }
+            }
+            "mint" => match entry.meta::<MintOperationMeta>().variant {
+                MintOperationMetaVariant::Reissuance { .. } => {
+                    match decode::<ReissueExternalNotesState>(state)? {
+                        ReissueExternalNotesState::Created | ReissueExternalNotesState::Issuing => {
+                            Pending
+                        }
+                        ReissueExternalNotesState::Done => Succeeded,
+                        ReissueExternalNotesState::Failed(_) => Failed,
+                    }
+                }
+                MintOperationMetaVariant::SpendOOB { .. } => {
+                    match decode::<SpendOOBState>(state)? {
+                        SpendOOBState::Created | SpendOOBState::UserCanceledProcessing => Pending,
+                        // Canceling failed because the recipient reissued the
+                        // notes
+                        SpendOOBState::Success | SpendOOBState::UserCanceledFailure => Succeeded,
+                        SpendOOBState::Refunded | SpendOOBState::UserCanceledSuccess => Refunded,
+                    }
+                }
+            },
+            "wallet" => match entry.meta::<WalletOperationMeta>().variant {
+                WalletOperationMetaVariant::Deposit { .. } => match decode(state) {
+                    Some(
+                        DepositStateV2::WaitingForTransaction
+                        | DepositStateV2::WaitingForConfirmation { .. }
+                        | DepositStateV2::Confirmed { .. },
+                    ) => Pending,
+                    Some(DepositStateV2::Claimed { .. }) => Succeeded,
+                    Some(DepositStateV2::Failed(_)) => Failed,
+                    // Outcome of a deposit made before fedimint 0.4.2
+                    None => match decode::<DepositStateV1>(state)? {
+                        DepositStateV1::Claimed(_) => Succeeded,
+                        DepositStateV1::Failed(_) => Failed,
+                        _ => Pending,
+                    },
+                },
+                WalletOperationMetaVariant::Withdraw { .. } => {
+                    match decode::<WithdrawState>(state)? {
+                        WithdrawState::Created => Pending,
+                        WithdrawState::Succeeded(_) => Succeeded,
+                        WithdrawState::Failed(_) => Failed,
+                    }
+                }
+                _ => return None,
+            },
+            _ => return None,
+        };
+        Some(status)
+    }
+}
+
+/// Decodes a module state serialized to JSON, `None` if it's of another type
+/// or of an older version of the module.
+fn decode<T: DeserializeOwned>(state: &Value) -> Option<T> {
+    serde_json::from_value(state.clone()).ok()
 }
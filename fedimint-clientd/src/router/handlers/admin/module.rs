@@ -1,3 +1,5 @@
+use std::ffi::OsString;
+
 use anyhow::anyhow;
 use axum::extract::State;
 use axum::http::StatusCode;
@@ -12,8 +14,10 @@ use tracing::info;
 use crate::error::AppError;
 use crate::state::AppState;
 
+/// Selects a module by instance id (`{"id": 3}`) or by kind, in which case
+/// the first instance of that kind is used (`{"kind": "meta"}`)
 #[derive(Debug, Clone, Deserialize)]
-#[allow(unused)]
+#[serde(rename_all = "camelCase")]
 pub enum ModuleSelector {
     Id(ModuleInstanceId),
     Kind(ModuleKind),
@@ -24,35 +28,51 @@ pub enum ModuleSelector {
 pub struct ModuleRequest {
     pub module: ModuleSelector,
     pub args: Vec<String>,
-    pub federation_id: FederationId,
+    pub federation_id: Option<FederationId>,
 }
 
-async fn _module(_client: ClientHandleArc, req: ModuleRequest) -> Result<(), AppError> {
+/// Dispatches `args` to the selected module's CLI command handler, like
+/// `fedimint-cli module`, and returns its JSON output.
+async fn _module(client: ClientHandleArc, req: ModuleRequest) -> Result<Value, AppError> {
     info!(
         "Module request: module: {:?}, args: {:?}, federation_id: {:?}",
         req.module, req.args, req.federation_id
     );
-    // TODO: Figure out how to impl this
-    Err(AppError::new(
-        StatusCode::INTERNAL_SERVER_ERROR,
-        anyhow!("Not implemented"),
-    ))
+    let module_instance_id = match req.module {
+        ModuleSelector::Id(id) => id,
+        ModuleSelector::Kind(kind) => client.get_first_instance(&kind).ok_or_else(|| {
+            AppError::new(
+                StatusCode::BAD_REQUEST,
+                anyhow!("No module with kind {kind} found"),
+            )
+        })?,
+    };
+    let module = client
+        .get_module_client_dyn(module_instance_id)
+        .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, anyhow!("Module not found: {e}")))?;
+
+    let args = req.args.into_iter().map(OsString::from).collect::<Vec<_>>();
+    module
+        .handle_cli_command(&args)
+        .await
+        .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, e))
 }
 
 pub async fn handle_ws(state: AppState, v: Value) -> Result<Value, AppError> {
     let v = serde_json::from_value::<ModuleRequest>(v)
         .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, anyhow!("Invalid request: {}", e)))?;
     let client = state.get_client(v.federation_id).await?;
-    _module(client, v).await?;
-    Ok(json!(()))
+    let output = _module(client, v).await?;
+    let output_json = json!(output);
+    Ok(output_json)
 }
 
 #[axum_macros::debug_handler]
 pub async fn handle_rest(
     State(state): State<AppState>,
     Json(req): Json<ModuleRequest>,
-) -> Result<Json<()>, AppError> {
+) -> Result<Json<Value>, AppError> {
     let client = state.get_client(req.federation_id).await?;
-    _module(client, req).await?;
-    Ok(Json(()))
+    let output = _module(client, req).await?;
+    Ok(Json(output))
 }
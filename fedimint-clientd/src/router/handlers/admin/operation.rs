@@ -0,0 +1,296 @@
+use std::time::SystemTime;
+
+use anyhow::anyhow;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use futures_util::stream::BoxStream;
+use multimint::fedimint_client::oplog::OperationLogEntry;
+use multimint::fedimint_client::ClientHandleArc;
+use multimint::fedimint_core::config::FederationId;
+use multimint::fedimint_core::core::OperationId;
+use multimint::fedimint_core::Amount;
+use multimint::fedimint_ln_client::{
+    LightningClientModule, LightningOperationMeta, LightningOperationMetaVariant,
+};
+use multimint::fedimint_mint_client::{
+    MintClientModule, MintOperationMeta, MintOperationMetaVariant,
+};
+use multimint::fedimint_wallet_client::{
+    WalletClientModule, WalletOperationMeta, WalletOperationMetaVariant,
+};
+use multimint::operations::{latest_update, PAGE_SIZE};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::list_operations::format_creation_time;
+use super::OperationStatus;
+use crate::error::AppError;
+use crate::state::AppState;
+
+/// Number of most recent operations searched for an operation's creation time
+const CREATION_TIME_SEARCH_LIMIT: usize = 10 * PAGE_SIZE;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationRequest {
+    pub operation_id: OperationId,
+    pub federation_id: Option<FederationId>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct OperationResponse {
+    pub id: OperationId,
+    pub federation_id: FederationId,
+    pub operation_kind: String,
+    pub operation_meta: Value,
+    /// Absent for operations older than the `CREATION_TIME_SEARCH_LIMIT` most
+    /// recent ones
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub creation_time: Option<String>,
+    /// Latest state update of the operation's module state machine
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub state: Option<Value>,
+    pub status: OperationStatus,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub amount_msat: Option<Amount>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee_msat: Option<Amount>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub outcome: Option<Value>,
+}
+
+/// Amount and fee of an operation as far as they are known from its meta.
+pub(crate) fn operation_amounts(entry: &OperationLogEntry) -> (Option<Amount>, Option<Amount>) {
+    match entry.operation_module_kind() {
+        "ln" => match entry.meta::<LightningOperationMeta>().variant {
+            LightningOperationMetaVariant::Pay(pay) => (
+                pay.invoice.amount_milli_satoshis().map(Amount::from_msats),
+                Some(pay.fee),
+            ),
+            LightningOperationMetaVariant::Receive { invoice, .. } => (
+                invoice.amount_milli_satoshis().map(Amount::from_msats),
+                None,
+            ),
+            LightningOperationMetaVariant::Claim { .. } => (None, None),
+        },
+        #[cfg(feature = "lnv2")]
+        "lnv2" => {
+            use multimint::fedimint_lnv2_client as lnv2;
+
+            match entry.meta::<lnv2::LightningOperationMeta>() {
+                // The contract funds the invoice amount and the gateway fee
+                lnv2::LightningOperationMeta::Send(send) => {
+                    let amount = send.invoice.amount_milli_satoshis().map(Amount::from_msats);
+                    let fee = amount.and_then(|amount| send.contract.amount.checked_sub(amount));
+                    (amount, fee)
+                }
+                lnv2::LightningOperationMeta::Receive(receive) => {
+                    (Some(receive.contract.commitment.amount), None)
+                }
+            }
+        }
+        "mint" => {
+            let meta = entry.meta::<MintOperationMeta>();
+            (Some(meta.amount), None)
+        }
+        "wallet" => match entry.meta::<WalletOperationMeta>().variant {
+            WalletOperationMetaVariant::Withdraw { amount, fee, .. } => (
+                Some(Amount::from_sats(amount.to_sat())),
+                Some(Amount::from_sats(fee.amount().to_sat())),
+            ),
+            _ => (None, None),
+        },
+        _ => (None, None),
+    }
+}
+
+/// Latest update of `updates` as JSON.
+async fn latest_update_json<T: Serialize>(updates: BoxStream<'_, T>) -> Option<Value> {
+    latest_update(updates)
+        .await
+        .and_then(|update| serde_json::to_value(update).ok())
+}
+
+/// Subscribes to the module stream matching the operation and returns its
+/// current state.
+pub(crate) async fn current_state(
+    client: &ClientHandleArc,
+    operation_id: OperationId,
+    entry: &OperationLogEntry,
+) -> anyhow::Result<Option<Value>> {
+    let state = match entry.operation_module_kind() {
+        "ln" => {
+            let ln = client.get_first_module::<LightningClientModule>();
+            match entry.meta::<LightningOperationMeta>().variant {
+                LightningOperationMetaVariant::Pay(pay) if pay.is_internal_payment => {
+                    latest_update_json(ln.subscribe_internal_pay(operation_id).await?.into_stream())
+                        .await
+                }
+                LightningOperationMetaVariant::Pay(_) => {
+                    latest_update_json(ln.subscribe_ln_pay(operation_id).await?.into_stream()).await
+                }
+                LightningOperationMetaVariant::Receive { .. } => {
+                    latest_update_json(ln.subscribe_ln_receive(operation_id).await?.into_stream())
+                        .await
+                }
+                LightningOperationMetaVariant::Claim { .. } => {
+                    latest_update_json(ln.subscribe_ln_claim(operation_id).await?.into_stream())
+                        .await
+                }
+            }
+        }
+        #[cfg(feature = "lnv2")]
+        "lnv2" => {
+            use multimint::fedimint_lnv2_client as lnv2;
+
+            let lnv2 = client.get_first_module::<lnv2::LightningClientModule>();
+            match entry.meta::<lnv2::LightningOperationMeta>() {
+                lnv2::LightningOperationMeta::Send(_) => {
+                    latest_update_json(lnv2.subscribe_send(operation_id).await?.into_stream()).await
+                }
+                lnv2::LightningOperationMeta::Receive(_) => {
+                    latest_update_json(lnv2.subscribe_receive(operation_id).await?.into_stream())
+                        .await
+                }
+            }
+        }
+        "mint" => {
+            let mint = client.get_first_module::<MintClientModule>();
+            match entry.meta::<MintOperationMeta>().variant {
+                MintOperationMetaVariant::Reissuance { .. } => {
+                    latest_update_json(
+                        mint.subscribe_reissue_external_notes(operation_id)
+                            .await?
+                            .into_stream(),
+                    )
+                    .await
+                }
+                MintOperationMetaVariant::SpendOOB { .. } => {
+                    latest_update_json(
+                        mint.subscribe_spend_notes(operation_id)
+                            .await?
+                            .into_stream(),
+                    )
+                    .await
+                }
+            }
+        }
+        "wallet" => {
+            let wallet = client.get_first_module::<WalletClientModule>();
+            match entry.meta::<WalletOperationMeta>().variant {
+                WalletOperationMetaVariant::Deposit { .. } => {
+                    latest_update_json(wallet.subscribe_deposit(operation_id).await?.into_stream())
+                        .await
+                }
+                WalletOperationMetaVariant::Withdraw { .. } => {
+                    latest_update_json(
+                        wallet
+                            .subscribe_withdraw_updates(operation_id)
+                            .await?
+                            .into_stream(),
+                    )
+                    .await
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+
+    Ok(state)
+}
+
+/// The operation log entry doesn't store when it was created, so the
+/// chronological index is scanned for it. Only the most recent
+/// `CREATION_TIME_SEARCH_LIMIT` operations are searched to keep lookups cheap
+/// on large logs.
+async fn find_creation_time(
+    client: &ClientHandleArc,
+    operation_id: OperationId,
+) -> Option<SystemTime> {
+    let mut cursor = None;
+    let mut searched = 0;
+    while searched < CREATION_TIME_SEARCH_LIMIT {
+        let page = client
+            .operation_log()
+            .list_operations(PAGE_SIZE, cursor)
+            .await;
+        let exhausted = page.len() < PAGE_SIZE;
+        searched += page.len();
+
+        for (key, _) in page {
+            if key.operation_id == operation_id {
+                return Some(key.creation_time);
+            }
+            cursor = Some(key);
+        }
+
+        if exhausted {
+            return None;
+        }
+    }
+    None
+}
+
+async fn _operation(
+    client: ClientHandleArc,
+    req: OperationRequest,
+) -> Result<OperationResponse, AppError> {
+    let entry = client
+        .operation_log()
+        .get_operation(req.operation_id)
+        .await
+        .ok_or_else(|| {
+            AppError::new(
+                StatusCode::NOT_FOUND,
+                anyhow!("Operation not found: {:?}", req.operation_id),
+            )
+        })?;
+
+    let state = current_state(&client, req.operation_id, &entry).await?;
+    // Subscribing may have stored the final outcome, so read it again
+    let outcome = client
+        .operation_log()
+        .get_operation(req.operation_id)
+        .await
+        .and_then(|entry| entry.outcome::<Value>());
+    let creation_time = find_creation_time(&client, req.operation_id)
+        .await
+        .map(format_creation_time)
+        .transpose()?;
+    let (amount_msat, fee_msat) = operation_amounts(&entry);
+
+    Ok(OperationResponse {
+        id: req.operation_id,
+        federation_id: client.federation_id(),
+        operation_kind: entry.operation_module_kind().to_owned(),
+        operation_meta: entry.meta(),
+        creation_time,
+        status: OperationStatus::from_state(&entry, outcome.as_ref().or(state.as_ref())),
+        state,
+        amount_msat,
+        fee_msat,
+        outcome,
+    })
+}
+
+pub async fn handle_ws(state: AppState, v: Value) -> Result<Value, AppError> {
+    let v = serde_json::from_value::<OperationRequest>(v)
+        .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, anyhow!("Invalid request: {}", e)))?;
+    let client = state.get_client(v.federation_id).await?;
+    let operation = _operation(client, v).await?;
+    let operation_json = json!(operation);
+    Ok(operation_json)
+}
+
+#[axum_macros::debug_handler]
+pub async fn handle_rest(
+    State(state): State<AppState>,
+    Json(req): Json<OperationRequest>,
+) -> Result<Json<OperationResponse>, AppError> {
+    let client = state.get_client(req.federation_id).await?;
+    let operation = _operation(client, req).await?;
+    Ok(Json(operation))
+}
@@ -0,0 +1,121 @@
+use std::collections::BTreeMap;
+
+use anyhow::anyhow;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use multimint::fedimint_api_client::download_from_invite_code;
+use multimint::fedimint_core::config::FederationId;
+use multimint::fedimint_core::core::ModuleInstanceId;
+use multimint::fedimint_core::invite_code::InviteCode;
+use multimint::fedimint_core::PeerId;
+use multimint::MultiMint;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::error::AppError;
+use crate::state::AppState;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewRequest {
+    pub invite_code: InviteCode,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PreviewResponse {
+    pub federation_id: FederationId,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub federation_name: Option<String>,
+    pub guardians: Vec<Guardian>,
+    pub modules: Vec<Module>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub network: Option<String>,
+    pub meta: BTreeMap<String, String>,
+    /// Whether the multimint already has a client for this federation
+    pub already_joined: bool,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Guardian {
+    pub peer_id: PeerId,
+    pub name: String,
+    pub url: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Module {
+    pub instance_id: ModuleInstanceId,
+    pub kind: String,
+}
+
+async fn _preview(multimint: MultiMint, req: PreviewRequest) -> Result<PreviewResponse, AppError> {
+    let config = download_from_invite_code(&req.invite_code)
+        .await
+        .map_err(|e| {
+            AppError::new(
+                StatusCode::BAD_GATEWAY,
+                anyhow!("Failed to download the federation config: {e}"),
+            )
+        })?;
+    let federation_id = config.global.calculate_federation_id();
+    let json_config = config.to_json();
+
+    let guardians = config
+        .global
+        .api_endpoints
+        .iter()
+        .map(|(peer_id, peer_url)| Guardian {
+            peer_id: *peer_id,
+            name: peer_url.name.clone(),
+            url: peer_url.url.to_string(),
+        })
+        .collect();
+
+    let modules = json_config
+        .modules
+        .iter()
+        .map(|(instance_id, module)| Module {
+            instance_id: *instance_id,
+            kind: module.kind().to_string(),
+        })
+        .collect();
+
+    let network = json_config
+        .modules
+        .values()
+        .find(|module| module.kind().as_str() == "wallet")
+        .and_then(|module| module.value().get("network"))
+        .and_then(Value::as_str)
+        .map(str::to_owned);
+
+    Ok(PreviewResponse {
+        federation_id,
+        federation_name: config.global.meta.get("federation_name").cloned(),
+        guardians,
+        modules,
+        network,
+        meta: config.global.meta.clone(),
+        already_joined: multimint.has(&federation_id).await,
+    })
+}
+
+pub async fn handle_ws(state: AppState, v: Value) -> Result<Value, AppError> {
+    let v = serde_json::from_value::<PreviewRequest>(v)
+        .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, anyhow!("Invalid request: {}", e)))?;
+    let preview = _preview(state.multimint, v).await?;
+    let preview_json = json!(preview);
+    Ok(preview_json)
+}
+
+#[axum_macros::debug_handler]
+pub async fn handle_rest(
+    State(state): State<AppState>,
+    Json(req): Json<PreviewRequest>,
+) -> Result<Json<PreviewResponse>, AppError> {
+    let preview = _preview(state.multimint, req).await?;
+    Ok(Json(preview))
+}
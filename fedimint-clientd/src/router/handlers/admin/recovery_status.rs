@@ -0,0 +1,83 @@
+use anyhow::anyhow;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use multimint::fedimint_core::config::FederationId;
+use multimint::types::RecoveryStatus;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use crate::error::AppError;
+use crate::state::AppState;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryStatusRequest {
+    pub federation_id: Option<FederationId>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RecoveryStatusResponse {
+    pub federation_id: FederationId,
+    pub recovering: bool,
+    /// Absent if the client wasn't recovered since the server started
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<RecoveryStatus>,
+}
+
+async fn _recovery_status(
+    state: &AppState,
+    req: RecoveryStatusRequest,
+) -> Result<RecoveryStatusResponse, AppError> {
+    let federation_id = state
+        .get_client_even_if_recovering(req.federation_id)
+        .await?
+        .federation_id();
+
+    let status = state.multimint.recovery_status(&federation_id).await;
+    Ok(RecoveryStatusResponse {
+        federation_id,
+        recovering: status.as_ref().is_some_and(|status| !status.complete),
+        status,
+    })
+}
+
+pub async fn handle_ws(state: AppState, v: Value) -> Result<Value, AppError> {
+    let v = serde_json::from_value::<RecoveryStatusRequest>(v)
+        .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, anyhow!("Invalid request: {}", e)))?;
+    let status = _recovery_status(&state, v).await?;
+    let status_json = json!(status);
+    Ok(status_json)
+}
+
+#[axum_macros::debug_handler]
+pub async fn handle_rest(
+    State(state): State<AppState>,
+    Json(req): Json<RecoveryStatusRequest>,
+) -> Result<Json<RecoveryStatusResponse>, AppError> {
+    let status = _recovery_status(&state, req).await?;
+    Ok(Json(status))
+}
+
+#[cfg(test)]
+mod tests {
+    use multimint::fedimint_core::db::mem_impl::MemDatabase;
+    use multimint::fedimint_core::db::Database;
+    use multimint::MultiMint;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn requests_without_a_default_federation_are_rejected() {
+        let multimint = MultiMint::builder()
+            .without_env_mnemonic()
+            .build_with_database(Database::new(MemDatabase::new(), Default::default()))
+            .await
+            .unwrap();
+        let state = AppState::with_multimint(multimint, Default::default(), Default::default());
+
+        let err = handle_ws(state, json!({})).await.unwrap_err();
+        assert_eq!(err.status, StatusCode::BAD_REQUEST);
+    }
+}
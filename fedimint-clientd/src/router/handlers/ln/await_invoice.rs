@@ -11,6 +11,7 @@ use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use tracing::{debug, error, info};
 
+use super::LnModule;
 use crate::error::AppError;
 use crate::state::AppState;
 
@@ -18,19 +19,55 @@ use crate::state::AppState;
 #[serde(rename_all = "camelCase")]
 pub struct AwaitInvoiceRequest {
     pub operation_id: OperationId,
-    pub federation_id: FederationId,
+    pub federation_id: Option<FederationId>,
 }
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct AwaitInvoiceResponse {
-    pub status: LnReceiveState,
+    pub status: InvoiceStatus,
 }
 
-async fn _await_invoice(
+/// Final state of the invoice, in the format of the module that created it
+#[derive(Debug, Serialize)]
+#[serde(untagged)]
+pub enum InvoiceStatus {
+    Lnv1(LnReceiveState),
+    #[cfg(feature = "lnv2")]
+    Lnv2(multimint::fedimint_lnv2_client::ReceiveState),
+}
+
+pub(crate) async fn _await_invoice(
     client: ClientHandleArc,
     req: AwaitInvoiceRequest,
 ) -> Result<AwaitInvoiceResponse, AppError> {
+    let operation = client
+        .operation_log()
+        .get_operation(req.operation_id)
+        .await
+        .ok_or_else(|| {
+            AppError::new(
+                StatusCode::BAD_REQUEST,
+                anyhow!("Operation {:?} not found", req.operation_id),
+            )
+        })?;
+    match LnModule::from_operation_kind(operation.operation_module_kind()) {
+        Some(LnModule::Lnv1) => {}
+        #[cfg(feature = "lnv2")]
+        Some(LnModule::Lnv2) => {
+            let status = super::lnv2::await_invoice(&client, req.operation_id).await?;
+            return Ok(AwaitInvoiceResponse {
+                status: InvoiceStatus::Lnv2(status),
+            });
+        }
+        _ => {
+            return Err(AppError::new(
+                StatusCode::BAD_REQUEST,
+                anyhow!("Operation is not a lightning operation"),
+            ))
+        }
+    }
+
     let lightning_module = &client.get_first_module::<LightningClientModule>();
     let mut updates = lightning_module
         .subscribe_ln_receive(req.operation_id)
@@ -44,11 +81,15 @@ async fn _await_invoice(
         debug!("Update: {update:?}");
         match &update {
             LnReceiveState::Claimed => {
-                return Ok(AwaitInvoiceResponse { status: update });
+                return Ok(AwaitInvoiceResponse {
+                    status: InvoiceStatus::Lnv1(update),
+                });
             }
             LnReceiveState::Canceled { reason } => {
                 error!("Invoice canceled: {}", reason);
-                return Ok(AwaitInvoiceResponse { status: update });
+                return Ok(AwaitInvoiceResponse {
+                    status: InvoiceStatus::Lnv1(update),
+                });
             }
             _ => {}
         }
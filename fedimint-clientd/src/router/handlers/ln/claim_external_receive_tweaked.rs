@@ -6,11 +6,12 @@ use futures_util::StreamExt;
 use multimint::fedimint_client::ClientHandleArc;
 use multimint::fedimint_core::config::FederationId;
 use multimint::fedimint_core::secp256k1::{KeyPair, Secp256k1, SecretKey};
-use multimint::fedimint_ln_client::{LightningClientModule, LnReceiveState};
+use multimint::fedimint_ln_client::LnReceiveState;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use tracing::{debug, error, info};
 
+use super::lnv1_module;
 use crate::error::AppError;
 use crate::state::AppState;
 
@@ -19,7 +20,7 @@ use crate::state::AppState;
 pub struct ClaimExternalReceiveTweakedRequest {
     pub tweaks: Vec<u64>,
     pub private_key: SecretKey,
-    pub federation_id: FederationId,
+    pub federation_id: Option<FederationId>,
 }
 
 #[derive(Debug, Serialize)]
@@ -34,7 +35,7 @@ async fn _await_claim_external_receive_tweaked(
 ) -> Result<ClaimExternalReceiveTweakedResponse, AppError> {
     let secp = Secp256k1::new();
     let key_pair = KeyPair::from_secret_key(&secp, &req.private_key);
-    let lightning_module = &client.get_first_module::<LightningClientModule>();
+    let lightning_module = &lnv1_module(&client)?;
     let operation_id = lightning_module
         .scan_receive_for_user_tweaked(key_pair, req.tweaks, ())
         .await;
@@ -13,6 +13,7 @@ use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use tracing::error;
 
+use super::{select_ln_module, LnModule};
 use crate::error::AppError;
 use crate::state::AppState;
 
@@ -22,8 +23,10 @@ pub struct LnInvoiceRequest {
     pub amount_msat: Amount,
     pub description: String,
     pub expiry_time: Option<u64>,
-    pub gateway_id: PublicKey,
-    pub federation_id: FederationId,
+    pub gateway_id: Option<PublicKey>,
+    pub federation_id: Option<FederationId>,
+    /// Lightning module to use, defaults to LNv2 if the federation offers it
+    pub module: Option<LnModule>,
 }
 
 #[derive(Debug, Serialize)]
@@ -31,18 +34,35 @@ pub struct LnInvoiceRequest {
 pub struct LnInvoiceResponse {
     pub operation_id: OperationId,
     pub invoice: String,
+    pub module: LnModule,
 }
 
-async fn _invoice(
+pub(crate) async fn _invoice(
+    state: &AppState,
     client: ClientHandleArc,
     req: LnInvoiceRequest,
 ) -> Result<LnInvoiceResponse, AppError> {
+    state.limits.check_invoice(req.amount_msat)?;
+    let module = select_ln_module(state, &client, req.module, req.gateway_id)?;
+    #[cfg(feature = "lnv2")]
+    if module == LnModule::Lnv2 {
+        let (operation_id, invoice) =
+            super::lnv2::invoice(&client, req.amount_msat, req.description, req.expiry_time)
+                .await?;
+        return Ok(LnInvoiceResponse {
+            operation_id,
+            invoice: invoice.to_string(),
+            module,
+        });
+    }
+
+    let gateway_id = state.gateway_policy.resolve(req.gateway_id)?;
     let lightning_module = client.get_first_module::<LightningClientModule>();
     let gateway = lightning_module
-        .select_gateway(&req.gateway_id)
+        .select_gateway(&gateway_id)
         .await
         .ok_or_else(|| {
-            error!("Failed to select gateway: {}", req.gateway_id);
+            error!("Failed to select gateway: {}", gateway_id);
             AppError::new(
                 StatusCode::INTERNAL_SERVER_ERROR,
                 anyhow!("Failed to select gateway"),
@@ -61,6 +81,7 @@ async fn _invoice(
     Ok(LnInvoiceResponse {
         operation_id,
         invoice: invoice.to_string(),
+        module,
     })
 }
 
@@ -68,7 +89,7 @@ pub async fn handle_ws(state: AppState, v: Value) -> Result<Value, AppError> {
     let v = serde_json::from_value::<LnInvoiceRequest>(v)
         .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, anyhow!("Invalid request: {}", e)))?;
     let client = state.get_client(v.federation_id).await?;
-    let invoice = _invoice(client, v).await?;
+    let invoice = _invoice(&state, client, v).await?;
     let invoice_json = json!(invoice);
     Ok(invoice_json)
 }
@@ -79,6 +100,6 @@ pub async fn handle_rest(
     Json(req): Json<LnInvoiceRequest>,
 ) -> Result<Json<LnInvoiceResponse>, AppError> {
     let client = state.get_client(req.federation_id).await?;
-    let invoice = _invoice(client, req).await?;
+    let invoice = _invoice(&state, client, req).await?;
     Ok(Json(invoice))
 }
@@ -7,12 +7,12 @@ use multimint::fedimint_core::config::FederationId;
 use multimint::fedimint_core::core::OperationId;
 use multimint::fedimint_core::secp256k1::PublicKey;
 use multimint::fedimint_core::Amount;
-use multimint::fedimint_ln_client::LightningClientModule;
 use multimint::fedimint_ln_common::lightning_invoice::{Bolt11InvoiceDescription, Description};
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use tracing::error;
 
+use super::lnv1_module;
 use crate::error::AppError;
 use crate::state::AppState;
 
@@ -24,8 +24,8 @@ pub struct LnInvoiceExternalPubkeyTweakedRequest {
     pub expiry_time: Option<u64>,
     pub external_pubkey: PublicKey,
     pub tweak: u64,
-    pub gateway_id: PublicKey,
-    pub federation_id: FederationId,
+    pub gateway_id: Option<PublicKey>,
+    pub federation_id: Option<FederationId>,
 }
 
 #[derive(Debug, Serialize)]
@@ -36,15 +36,18 @@ pub struct LnInvoiceExternalPubkeyTweakedResponse {
 }
 
 async fn _invoice(
+    state: &AppState,
     client: ClientHandleArc,
     req: LnInvoiceExternalPubkeyTweakedRequest,
 ) -> Result<LnInvoiceExternalPubkeyTweakedResponse, AppError> {
-    let lightning_module = client.get_first_module::<LightningClientModule>();
+    state.limits.check_invoice(req.amount_msat)?;
+    let gateway_id = state.gateway_policy.resolve(req.gateway_id)?;
+    let lightning_module = lnv1_module(&client)?;
     let gateway = lightning_module
-        .select_gateway(&req.gateway_id)
+        .select_gateway(&gateway_id)
         .await
         .ok_or_else(|| {
-            error!("Failed to select gateway: {}", req.gateway_id);
+            error!("Failed to select gateway: {}", gateway_id);
             AppError::new(
                 StatusCode::INTERNAL_SERVER_ERROR,
                 anyhow!("Failed to select gateway"),
@@ -72,7 +75,7 @@ pub async fn handle_ws(state: AppState, v: Value) -> Result<Value, AppError> {
     let v = serde_json::from_value::<LnInvoiceExternalPubkeyTweakedRequest>(v)
         .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, anyhow!("Invalid request: {}", e)))?;
     let client = state.get_client(v.federation_id).await?;
-    let invoice = _invoice(client, v).await?;
+    let invoice = _invoice(&state, client, v).await?;
     let invoice_json = json!(invoice);
     Ok(invoice_json)
 }
@@ -83,6 +86,6 @@ pub async fn handle_rest(
     Json(req): Json<LnInvoiceExternalPubkeyTweakedRequest>,
 ) -> Result<Json<LnInvoiceExternalPubkeyTweakedResponse>, AppError> {
     let client = state.get_client(req.federation_id).await?;
-    let invoice = _invoice(client, req).await?;
+    let invoice = _invoice(&state, client, req).await?;
     Ok(Json(invoice))
 }
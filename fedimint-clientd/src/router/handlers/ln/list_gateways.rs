@@ -4,21 +4,21 @@ use axum::http::StatusCode;
 use axum::Json;
 use multimint::fedimint_client::ClientHandleArc;
 use multimint::fedimint_core::config::FederationId;
-use multimint::fedimint_ln_client::LightningClientModule;
 use serde::Deserialize;
 use serde_json::{json, Value};
 
+use super::lnv1_module;
 use crate::error::AppError;
 use crate::state::AppState;
 
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct ListGatewaysRequest {
-    pub federation_id: FederationId,
+    pub federation_id: Option<FederationId>,
 }
 
 async fn _list_gateways(client: ClientHandleArc) -> Result<Value, AppError> {
-    let lightning_module = client.get_first_module::<LightningClientModule>();
+    let lightning_module = lnv1_module(&client)?;
     let gateways = lightning_module.list_gateways().await;
     if gateways.is_empty() {
         return Ok(serde_json::to_value(Vec::<String>::new())?);
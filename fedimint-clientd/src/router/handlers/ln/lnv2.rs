@@ -0,0 +1,171 @@
+//! Invoice creation, payment and awaiting through the lightning v2 module.
+//!
+//! LNv2 gateways are addressed by their api url rather than a node pubkey, so
+//! the gateway is picked from the ones registered with the federation.
+//! Requests with a `gatewayId`, or when the gateway policy has an allowlist,
+//! use LNv1 instead, see `GatewayPolicy::allows_lnv2`.
+
+use anyhow::anyhow;
+use axum::http::StatusCode;
+use futures_util::StreamExt;
+use multimint::fedimint_client::ClientHandleArc;
+use multimint::fedimint_core::core::OperationId;
+use multimint::fedimint_core::util::SafeUrl;
+use multimint::fedimint_core::Amount;
+use multimint::fedimint_ln_common::lightning_invoice::Bolt11Invoice;
+use multimint::fedimint_lnv2_client::api::LnFederationApi;
+use multimint::fedimint_lnv2_client::{
+    Bolt11InvoiceDescription, LightningClientModule, LightningOperationMeta, PaymentFee,
+    ReceiveState, SendState,
+};
+use tracing::{debug, info};
+
+use super::pay::{LnPayResponse, StartedPayment};
+use super::LnModule;
+use crate::error::AppError;
+
+/// Invoice expiry used when the request doesn't set one, matching LNv1's
+const DEFAULT_EXPIRY_SECS: u32 = 60 * 60 * 24;
+
+/// The gateway registered with the most guardians.
+async fn select_gateway(client: &ClientHandleArc) -> Result<SafeUrl, AppError> {
+    let gateways = client
+        .get_first_module::<LightningClientModule>()
+        .api
+        .fetch_gateways()
+        .await
+        .map_err(|e| AppError::new(StatusCode::INTERNAL_SERVER_ERROR, anyhow!("{e}")))?;
+    gateways.into_iter().next().ok_or_else(|| {
+        AppError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            anyhow!("No LNv2 gateway is registered with the federation"),
+        )
+    })
+}
+
+pub async fn invoice(
+    client: &ClientHandleArc,
+    amount: Amount,
+    description: String,
+    expiry_time: Option<u64>,
+) -> Result<(OperationId, Bolt11Invoice), AppError> {
+    let expiry_secs = match expiry_time {
+        Some(expiry_time) => u32::try_from(expiry_time).map_err(|_| {
+            AppError::new(StatusCode::BAD_REQUEST, anyhow!("Expiry time is too large"))
+        })?,
+        None => DEFAULT_EXPIRY_SECS,
+    };
+    let gateway = select_gateway(client).await?;
+    let (invoice, operation_id) = client
+        .get_first_module::<LightningClientModule>()
+        .receive_internal(
+            gateway,
+            amount,
+            expiry_secs,
+            Bolt11InvoiceDescription::Direct(description),
+            PaymentFee::one_percent(),
+        )
+        .await
+        .map_err(|e| AppError::new(StatusCode::INTERNAL_SERVER_ERROR, anyhow!("{e}")))?;
+    Ok((operation_id, invoice))
+}
+
+/// Gateway fee of a payment: what the outgoing contract locks on top of the
+/// invoice's amount.
+async fn send_fee(
+    client: &ClientHandleArc,
+    operation_id: OperationId,
+    invoice_amount: Amount,
+) -> Option<Amount> {
+    let entry = client.operation_log().get_operation(operation_id).await?;
+    match entry.meta::<LightningOperationMeta>() {
+        LightningOperationMeta::Send(send) => send.contract.amount.checked_sub(invoice_amount),
+        LightningOperationMeta::Receive(_) => None,
+    }
+}
+
+/// Pays the invoice without waiting for the outcome. The payment's
+/// `contract_id` is the invoice's payment hash.
+pub async fn send(
+    client: &ClientHandleArc,
+    invoice: Bolt11Invoice,
+) -> Result<StartedPayment, AppError> {
+    let payment_hash = invoice.payment_hash().to_string();
+    let invoice_amount = Amount::from_msats(invoice.amount_milli_satoshis().unwrap_or(0));
+    let gateway = select_gateway(client).await?;
+    let operation_id = client
+        .get_first_module::<LightningClientModule>()
+        .send(gateway, invoice)
+        .await
+        .map_err(|e| AppError::new(StatusCode::INTERNAL_SERVER_ERROR, anyhow!("{e}")))?;
+    info!("LNv2 payment operation id: {operation_id:?}");
+    Ok(StartedPayment {
+        operation_id,
+        module: LnModule::Lnv2,
+        payment_type: None,
+        contract_id: payment_hash,
+        fee: send_fee(client, operation_id, invoice_amount).await,
+    })
+}
+
+/// Waits for the payment to succeed. The preimage and payment type are absent
+/// from the response as the LNv2 module doesn't expose them.
+pub async fn wait_for_send(
+    client: &ClientHandleArc,
+    payment: StartedPayment,
+) -> Result<LnPayResponse, AppError> {
+    let operation_id = payment.operation_id;
+    let mut updates = client
+        .get_first_module::<LightningClientModule>()
+        .subscribe_send(operation_id)
+        .await?
+        .into_stream();
+    while let Some(update) = updates.next().await {
+        debug!("Update: {update:?}");
+        match update {
+            SendState::Success => {
+                return Ok(LnPayResponse {
+                    operation_id,
+                    payment_type: None,
+                    contract_id: payment.contract_id,
+                    fee: payment.fee,
+                    preimage: None,
+                });
+            }
+            SendState::Refunded => Err(anyhow!("Payment was refunded"))?,
+            SendState::Failure => Err(anyhow!("Payment failed"))?,
+            SendState::Funding | SendState::Funded | SendState::Refunding => {}
+        }
+    }
+
+    Err(AppError::new(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        anyhow!("Unexpected end of stream"),
+    ))
+}
+
+/// Waits until the invoice is claimed, expires or fails
+pub async fn await_invoice(
+    client: &ClientHandleArc,
+    operation_id: OperationId,
+) -> Result<ReceiveState, AppError> {
+    let mut updates = client
+        .get_first_module::<LightningClientModule>()
+        .subscribe_receive(operation_id)
+        .await?
+        .into_stream();
+    while let Some(update) = updates.next().await {
+        debug!("Update: {update:?}");
+        match update {
+            ReceiveState::Claimed | ReceiveState::Expired | ReceiveState::Failure => {
+                return Ok(update);
+            }
+            ReceiveState::Pending | ReceiveState::Claiming => {}
+        }
+    }
+
+    Err(AppError::new(
+        StatusCode::INTERNAL_SERVER_ERROR,
+        anyhow!("Unexpected end of stream"),
+    ))
+}
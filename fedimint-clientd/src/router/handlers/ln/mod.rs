@@ -1,21 +1,109 @@
 use std::str::FromStr;
 
-use anyhow::{bail, Context};
+use anyhow::{anyhow, bail, Context};
+use axum::http::StatusCode;
 use futures_util::StreamExt;
-use multimint::fedimint_client::ClientHandleArc;
-use multimint::fedimint_core::Amount;
+use multimint::fedimint_client::{ClientHandleArc, ClientModuleInstance};
+use multimint::fedimint_core::core::ModuleKind;
+use multimint::fedimint_core::secp256k1::PublicKey;
 use multimint::fedimint_ln_client::{InternalPayState, LightningClientModule, LnPayState, PayType};
 use multimint::fedimint_ln_common::lightning_invoice::Bolt11Invoice;
+use serde::{Deserialize, Serialize};
 use tracing::{debug, info};
 
 use self::pay::{LnPayRequest, LnPayResponse};
+use crate::error::AppError;
+use crate::state::AppState;
 
 pub mod await_invoice;
 pub mod claim_external_receive_tweaked;
 pub mod invoice;
 pub mod invoice_external_pubkey_tweaked;
 pub mod list_gateways;
+#[cfg(feature = "lnv2")]
+pub mod lnv2;
 pub mod pay;
+pub mod refresh_gateways;
+
+/// The lightning module used to create, pay or await an invoice
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LnModule {
+    Lnv1,
+    Lnv2,
+}
+
+impl LnModule {
+    /// Modules tried in order when a request doesn't pick one
+    const PREFERENCE: [LnModule; 2] = [LnModule::Lnv2, LnModule::Lnv1];
+
+    fn kind(self) -> ModuleKind {
+        match self {
+            LnModule::Lnv1 => ModuleKind::from_static_str("ln"),
+            LnModule::Lnv2 => ModuleKind::from_static_str("lnv2"),
+        }
+    }
+
+    /// Whether the federation offers the module and this build can use it
+    fn is_available(self, client: &ClientHandleArc) -> bool {
+        if self == LnModule::Lnv2 && !cfg!(feature = "lnv2") {
+            return false;
+        }
+        client.get_first_instance(&self.kind()).is_some()
+    }
+
+    /// The module an operation was created by, if it's a lightning operation
+    pub fn from_operation_kind(kind: &str) -> Option<LnModule> {
+        [LnModule::Lnv1, LnModule::Lnv2]
+            .into_iter()
+            .find(|module| module.kind().as_str() == kind)
+    }
+}
+
+/// The federation's lightning v1 module, `400 Bad Request` if it has none.
+pub fn lnv1_module(
+    client: &ClientHandleArc,
+) -> Result<ClientModuleInstance<'_, LightningClientModule>, AppError> {
+    if !LnModule::Lnv1.is_available(client) {
+        return Err(AppError::new(
+            StatusCode::BAD_REQUEST,
+            anyhow!("Federation has no lightning module"),
+        ));
+    }
+    Ok(client.get_first_module::<LightningClientModule>())
+}
+
+/// Returns the requested lightning module, or the first available one in
+/// [`LnModule::PREFERENCE`] order.
+pub fn select_ln_module(
+    state: &AppState,
+    client: &ClientHandleArc,
+    requested: Option<LnModule>,
+    gateway_id: Option<PublicKey>,
+) -> Result<LnModule, AppError> {
+    let lnv2_allowed = state.gateway_policy.allows_lnv2(gateway_id);
+    match requested {
+        Some(LnModule::Lnv2) if !lnv2_allowed => Err(AppError::new(
+            StatusCode::FORBIDDEN,
+            anyhow!("LNv2 can't be used with a gateway id or a gateway allowlist"),
+        )),
+        Some(module) if module.is_available(client) => Ok(module),
+        Some(module) => Err(AppError::new(
+            StatusCode::BAD_REQUEST,
+            anyhow!("Lightning module {module:?} is not available for this federation"),
+        )),
+        None => LnModule::PREFERENCE
+            .into_iter()
+            .filter(|module| *module != LnModule::Lnv2 || lnv2_allowed)
+            .find(|module| module.is_available(client))
+            .ok_or_else(|| {
+                AppError::new(
+                    StatusCode::BAD_REQUEST,
+                    anyhow!("Federation has no lightning module"),
+                )
+            }),
+    }
+}
 
 pub async fn get_invoice(req: &LnPayRequest) -> anyhow::Result<Bolt11Invoice> {
     let info = req.payment_info.trim();
@@ -83,10 +171,10 @@ pub async fn wait_for_ln_payment(
                     InternalPayState::Preimage(preimage) => {
                         return Ok(Some(LnPayResponse {
                             operation_id,
-                            payment_type,
+                            payment_type: Some(payment_type),
                             contract_id,
-                            fee: Amount::ZERO,
-                            preimage: hex::encode(preimage.0),
+                            fee: None,
+                            preimage: Some(hex::encode(preimage.0)),
                         }));
                     }
                     InternalPayState::RefundSuccess { out_points, error } => {
@@ -124,10 +212,10 @@ pub async fn wait_for_ln_payment(
                     LnPayState::Success { preimage } => {
                         return Ok(Some(LnPayResponse {
                             operation_id,
-                            payment_type,
+                            payment_type: Some(payment_type),
                             contract_id,
-                            fee: Amount::ZERO,
-                            preimage,
+                            fee: None,
+                            preimage: Some(preimage),
                         }));
                     }
                     LnPayState::Refunded { gateway_error } => {
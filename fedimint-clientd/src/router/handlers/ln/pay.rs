@@ -8,12 +8,13 @@ use multimint::fedimint_core::core::OperationId;
 use multimint::fedimint_core::secp256k1::PublicKey;
 use multimint::fedimint_core::Amount;
 use multimint::fedimint_ln_client::{LightningClientModule, OutgoingLightningPayment, PayType};
+use multimint::fedimint_ln_common::lightning_invoice::RoutingFees;
 use serde::{Deserialize, Serialize};
 use serde_json::{json, Value};
 use tracing::{error, info};
 
 use crate::error::AppError;
-use crate::router::handlers::ln::{get_invoice, wait_for_ln_payment};
+use crate::router::handlers::ln::{get_invoice, select_ln_module, wait_for_ln_payment, LnModule};
 use crate::state::AppState;
 
 #[derive(Debug, Deserialize)]
@@ -22,34 +23,90 @@ pub struct LnPayRequest {
     pub payment_info: String,
     pub amount_msat: Option<Amount>,
     pub lnurl_comment: Option<String>,
-    pub gateway_id: PublicKey,
-    pub federation_id: FederationId,
+    pub gateway_id: Option<PublicKey>,
+    pub federation_id: Option<FederationId>,
+    /// Lightning module to use, defaults to LNv2 if the federation offers it
+    pub module: Option<LnModule>,
 }
 
 #[derive(Debug, Serialize)]
 #[serde(rename_all = "camelCase")]
 pub struct LnPayResponse {
     pub operation_id: OperationId,
-    pub payment_type: PayType,
+    /// Absent for LNv2 payments
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub payment_type: Option<PayType>,
     pub contract_id: String,
-    pub fee: Amount,
-    pub preimage: String,
+    /// Gateway fee, absent if the module doesn't report it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fee: Option<Amount>,
+    /// Absent for LNv2 payments, the module doesn't expose it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub preimage: Option<String>,
 }
 
-async fn _pay(client: ClientHandleArc, req: LnPayRequest) -> Result<LnPayResponse, AppError> {
+/// A payment whose operation was created, see `start_pay`
+#[derive(Debug, Clone)]
+pub(crate) struct StartedPayment {
+    pub operation_id: OperationId,
+    pub module: LnModule,
+    /// Absent for LNv2 payments
+    pub payment_type: Option<PayType>,
+    pub contract_id: String,
+    /// Gateway fee, absent if the module doesn't report it
+    pub fee: Option<Amount>,
+}
+
+/// Gateway fee of an LNv1 payment of `amount`, as the module computes it
+fn gateway_fee(fees: &RoutingFees, amount: Amount) -> Amount {
+    let proportional = match u64::from(fees.proportional_millionths) {
+        0 => 0,
+        ppm => amount.msats / (1_000_000 / ppm),
+    };
+    Amount::from_msats(u64::from(fees.base_msat) + proportional)
+}
+
+/// Creates the payment's operation without waiting for its outcome. LNv1
+/// payments whose gateway fee exceeds `max_fee` are rejected before paying,
+/// LNv2 payments are only bounded by the module's own fee limit.
+pub(crate) async fn start_pay(
+    state: &AppState,
+    client: &ClientHandleArc,
+    req: LnPayRequest,
+    max_fee: Option<Amount>,
+) -> Result<StartedPayment, AppError> {
     let bolt11 = get_invoice(&req).await?;
+    let invoice_amount = Amount::from_msats(bolt11.amount_milli_satoshis().unwrap_or(0));
+    state.limits.check_send(invoice_amount)?;
     info!("Paying invoice: {bolt11}");
+    let module = select_ln_module(state, client, req.module, req.gateway_id)?;
+    #[cfg(feature = "lnv2")]
+    if module == LnModule::Lnv2 {
+        return super::lnv2::send(client, bolt11).await;
+    }
+    debug_assert_eq!(module, LnModule::Lnv1);
+
+    let gateway_id = state.gateway_policy.resolve(req.gateway_id)?;
     let lightning_module = client.get_first_module::<LightningClientModule>();
     let gateway = lightning_module
-        .select_gateway(&req.gateway_id)
+        .select_gateway(&gateway_id)
         .await
         .ok_or_else(|| {
-            error!("Failed to select gateway: {}", req.gateway_id);
+            error!("Failed to select gateway: {}", gateway_id);
             AppError::new(
                 StatusCode::INTERNAL_SERVER_ERROR,
                 anyhow!("Failed to select gateway"),
             )
         })?;
+    if let Some(max_fee) = max_fee {
+        let fee = gateway_fee(&gateway.fees, invoice_amount);
+        if fee > max_fee {
+            return Err(AppError::new(
+                StatusCode::BAD_REQUEST,
+                anyhow!("Gateway fee {fee} exceeds the maximum fee {max_fee}"),
+            ));
+        }
+    }
 
     let OutgoingLightningPayment {
         payment_type,
@@ -60,20 +117,58 @@ async fn _pay(client: ClientHandleArc, req: LnPayRequest) -> Result<LnPayRespons
         .await?;
     let operation_id = payment_type.operation_id();
     info!("Gateway fee: {fee}, payment operation id: {operation_id:?}");
+    Ok(StartedPayment {
+        operation_id,
+        module,
+        payment_type: Some(payment_type),
+        contract_id: contract_id.to_string(),
+        fee: Some(fee),
+    })
+}
+
+/// Waits for a started payment to succeed.
+pub(crate) async fn wait_for_pay(
+    client: &ClientHandleArc,
+    payment: StartedPayment,
+) -> Result<LnPayResponse, AppError> {
+    #[cfg(feature = "lnv2")]
+    if payment.module == LnModule::Lnv2 {
+        return super::lnv2::wait_for_send(client, payment).await;
+    }
+    debug_assert_eq!(payment.module, LnModule::Lnv1);
+    let payment_type = payment.payment_type.ok_or_else(|| {
+        AppError::new(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            anyhow!("Missing payment type of LNv1 payment"),
+        )
+    })?;
 
-    wait_for_ln_payment(&client, payment_type, contract_id.to_string(), false)
+    let response = wait_for_ln_payment(client, payment_type, payment.contract_id, false)
         .await?
         .ok_or_else(|| {
             error!("Payment failed");
             AppError::new(StatusCode::INTERNAL_SERVER_ERROR, anyhow!("Payment failed"))
-        })
+        })?;
+    Ok(LnPayResponse {
+        fee: payment.fee,
+        ..response
+    })
+}
+
+pub(crate) async fn _pay(
+    state: &AppState,
+    client: ClientHandleArc,
+    req: LnPayRequest,
+) -> Result<LnPayResponse, AppError> {
+    let payment = start_pay(state, &client, req, None).await?;
+    wait_for_pay(&client, payment).await
 }
 
 pub async fn handle_ws(state: AppState, v: Value) -> Result<Value, AppError> {
     let v = serde_json::from_value::<LnPayRequest>(v)
         .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, anyhow!("Invalid request: {}", e)))?;
     let client = state.get_client(v.federation_id).await?;
-    let pay = _pay(client, v).await?;
+    let pay = _pay(&state, client, v).await?;
     let pay_json = json!(pay);
     Ok(pay_json)
 }
@@ -84,6 +179,6 @@ pub async fn handle_rest(
     Json(req): Json<LnPayRequest>,
 ) -> Result<Json<LnPayResponse>, AppError> {
     let client = state.get_client(req.federation_id).await?;
-    let pay = _pay(client, req).await?;
+    let pay = _pay(&state, client, req).await?;
     Ok(Json(pay))
 }
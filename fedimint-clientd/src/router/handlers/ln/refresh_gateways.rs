@@ -0,0 +1,68 @@
+use anyhow::anyhow;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use multimint::fedimint_core::config::FederationId;
+use multimint::fedimint_ln_common::LightningGatewayAnnouncement;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::lnv1_module;
+use crate::error::AppError;
+use crate::state::AppState;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshGatewaysRequest {
+    pub federation_id: Option<FederationId>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RefreshGatewaysResponse {
+    pub federation_id: FederationId,
+    /// Unix timestamp of the refresh
+    pub last_refresh: Option<u64>,
+    pub gateways: Vec<LightningGatewayAnnouncement>,
+}
+
+async fn _refresh_gateways(
+    state: &AppState,
+    req: RefreshGatewaysRequest,
+) -> Result<RefreshGatewaysResponse, AppError> {
+    let client = state.get_client(req.federation_id).await?;
+    let federation_id = client.federation_id();
+    let lightning_module = lnv1_module(&client)?;
+    state.multimint.update_gateway_cache(&client).await?;
+
+    let gateways = lightning_module.list_gateways().await;
+    let last_refresh = state
+        .multimint
+        .gateway_cache_status()
+        .await
+        .get(&federation_id)
+        .and_then(|status| status.last_refresh);
+
+    Ok(RefreshGatewaysResponse {
+        federation_id,
+        last_refresh,
+        gateways,
+    })
+}
+
+pub async fn handle_ws(state: AppState, v: Value) -> Result<Value, AppError> {
+    let v = serde_json::from_value::<RefreshGatewaysRequest>(v)
+        .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, anyhow!("Invalid request: {}", e)))?;
+    let refresh = _refresh_gateways(&state, v).await?;
+    let refresh_json = json!(refresh);
+    Ok(refresh_json)
+}
+
+#[axum_macros::debug_handler]
+pub async fn handle_rest(
+    State(state): State<AppState>,
+    Json(req): Json<RefreshGatewaysRequest>,
+) -> Result<Json<RefreshGatewaysResponse>, AppError> {
+    let refresh = _refresh_gateways(&state, req).await?;
+    Ok(Json(refresh))
+}
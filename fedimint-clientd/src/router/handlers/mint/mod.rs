@@ -1,12 +1,23 @@
+use anyhow::anyhow;
+use axum::http::StatusCode;
+use multimint::fedimint_client::oplog::OperationLogEntry;
+use multimint::fedimint_client::ClientHandleArc;
+use multimint::fedimint_core::core::OperationId;
 use multimint::fedimint_core::TieredMulti;
-use multimint::fedimint_mint_client::SpendableNote;
+use multimint::fedimint_mint_client::{
+    MintOperationMeta, MintOperationMetaVariant, SpendOOBState, SpendableNote,
+};
 use serde::{Deserialize, Serialize};
 
+use crate::error::AppError;
+
 pub mod combine;
 pub mod decode_notes;
 pub mod encode_notes;
+pub mod reclaim;
 pub mod reissue;
 pub mod spend;
+pub mod spend_status;
 pub mod split;
 pub mod validate;
 
@@ -15,3 +26,58 @@ pub struct OOBNotesJson {
     federation_id_prefix: String,
     notes: TieredMulti<SpendableNote>,
 }
+
+/// What became of notes prepared with `spend`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SpendStatus {
+    /// Neither redeemed by the recipient nor reclaimed yet
+    Pending,
+    /// The recipient reissued the notes, they can't be reclaimed anymore
+    Redeemed,
+    /// The notes were reissued back into the wallet, either after the spend's
+    /// timeout or because they were reclaimed
+    Refunded,
+}
+
+impl From<&SpendOOBState> for SpendStatus {
+    fn from(state: &SpendOOBState) -> Self {
+        match state {
+            SpendOOBState::Created | SpendOOBState::UserCanceledProcessing => SpendStatus::Pending,
+            // Reissuing the notes failed in both cases because the recipient
+            // already spent them
+            SpendOOBState::Success | SpendOOBState::UserCanceledFailure => SpendStatus::Redeemed,
+            SpendOOBState::Refunded | SpendOOBState::UserCanceledSuccess => SpendStatus::Refunded,
+        }
+    }
+}
+
+/// Looks up an operation and checks that it is an out-of-band spend.
+pub(crate) async fn get_spend_operation(
+    client: &ClientHandleArc,
+    operation_id: OperationId,
+) -> Result<OperationLogEntry, AppError> {
+    let entry = client
+        .operation_log()
+        .get_operation(operation_id)
+        .await
+        .ok_or_else(|| {
+            AppError::new(
+                StatusCode::NOT_FOUND,
+                anyhow!("Operation not found: {:?}", operation_id),
+            )
+        })?;
+
+    let is_spend = entry.operation_module_kind() == "mint"
+        && matches!(
+            entry.meta::<MintOperationMeta>().variant,
+            MintOperationMetaVariant::SpendOOB { .. }
+        );
+    if !is_spend {
+        return Err(AppError::new(
+            StatusCode::BAD_REQUEST,
+            anyhow!("Operation {:?} is not an e-cash spend", operation_id),
+        ));
+    }
+    Ok(entry)
+}
@@ -0,0 +1,92 @@
+use std::time::Duration;
+
+use anyhow::anyhow;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use futures_util::StreamExt;
+use multimint::fedimint_client::ClientHandleArc;
+use multimint::fedimint_core::config::FederationId;
+use multimint::fedimint_core::core::OperationId;
+use multimint::fedimint_core::Amount;
+use multimint::fedimint_mint_client::{MintClientModule, MintOperationMeta, SpendOOBState};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use tracing::info;
+
+use super::{get_spend_operation, SpendStatus};
+use crate::error::AppError;
+use crate::state::AppState;
+
+/// How long to wait for the notes to be reissued before returning the spend's
+/// current state
+const RECLAIM_TIMEOUT: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReclaimRequest {
+    pub operation_id: OperationId,
+    pub federation_id: Option<FederationId>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReclaimResponse {
+    pub operation_id: OperationId,
+    pub amount_msat: Amount,
+    /// `refunded` if the notes are back in the wallet, `redeemed` if the
+    /// recipient spent them first, `pending` if reissuing them takes longer
+    /// than `RECLAIM_TIMEOUT`, see `/v2/mint/spend-status`
+    pub status: SpendStatus,
+    /// Latest state of the spend's state machine
+    pub state: SpendOOBState,
+}
+
+async fn _reclaim(
+    client: ClientHandleArc,
+    req: ReclaimRequest,
+) -> Result<ReclaimResponse, AppError> {
+    let entry = get_spend_operation(&client, req.operation_id).await?;
+    let mint = client.get_first_module::<MintClientModule>();
+
+    // Cancelling makes the spend's state machine reissue the notes right away
+    // instead of waiting for its timeout, it has no effect on finished spends
+    mint.try_cancel_spend_notes(req.operation_id).await;
+    let mut updates = mint
+        .subscribe_spend_notes(req.operation_id)
+        .await?
+        .into_stream();
+
+    let deadline = tokio::time::Instant::now() + RECLAIM_TIMEOUT;
+    let mut state = SpendOOBState::Created;
+    while let Ok(Some(update)) = tokio::time::timeout_at(deadline, updates.next()).await {
+        info!("Update: {update:?}");
+        state = update;
+    }
+
+    Ok(ReclaimResponse {
+        operation_id: req.operation_id,
+        amount_msat: entry.meta::<MintOperationMeta>().amount,
+        status: SpendStatus::from(&state),
+        state,
+    })
+}
+
+pub async fn handle_ws(state: AppState, v: Value) -> Result<Value, AppError> {
+    let v = serde_json::from_value::<ReclaimRequest>(v)
+        .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, anyhow!("Invalid request: {}", e)))?;
+    let client = state.get_client(v.federation_id).await?;
+    let reclaim = _reclaim(client, v).await?;
+    let reclaim_json = json!(reclaim);
+    Ok(reclaim_json)
+}
+
+#[axum_macros::debug_handler]
+pub async fn handle_rest(
+    State(state): State<AppState>,
+    Json(req): Json<ReclaimRequest>,
+) -> Result<Json<ReclaimResponse>, AppError> {
+    let client = state.get_client(req.federation_id).await?;
+    let reclaim = _reclaim(client, req).await?;
+    Ok(Json(reclaim))
+}
@@ -25,7 +25,7 @@ pub struct SpendRequest {
     pub allow_overpay: bool,
     pub timeout: u64,
     pub include_invite: bool,
-    pub federation_id: FederationId,
+    pub federation_id: Option<FederationId>,
 }
 
 #[derive(Debug, Serialize)]
@@ -35,8 +35,13 @@ pub struct SpendResponse {
     pub notes: OOBNotes,
 }
 
-async fn _spend(client: ClientHandleArc, req: SpendRequest) -> Result<SpendResponse, AppError> {
-    warn!("The client will try to double-spend these notes after the duration specified by the --timeout option to recover any unclaimed e-cash.");
+async fn _spend(
+    state: &AppState,
+    client: ClientHandleArc,
+    req: SpendRequest,
+) -> Result<SpendResponse, AppError> {
+    state.limits.check_send(req.amount_msat)?;
+    warn!("The client will try to double-spend these notes after the duration specified by the --timeout option to recover any unclaimed e-cash, use /v2/mint/reclaim to do so earlier.");
     let mint_module = client.get_first_module::<MintClientModule>();
     let timeout = Duration::from_secs(req.timeout);
     let (operation, notes) = if req.allow_overpay {
@@ -78,7 +83,7 @@ pub async fn handle_ws(state: AppState, v: Value) -> Result<Value, AppError> {
     let v = serde_json::from_value::<SpendRequest>(v)
         .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, anyhow!("Invalid request: {}", e)))?;
     let client = state.get_client(v.federation_id).await?;
-    let spend = _spend(client, v).await?;
+    let spend = _spend(&state, client, v).await?;
     let spend_json = json!(spend);
     Ok(spend_json)
 }
@@ -89,6 +94,6 @@ pub async fn handle_rest(
     Json(req): Json<SpendRequest>,
 ) -> Result<Json<SpendResponse>, AppError> {
     let client = state.get_client(req.federation_id).await?;
-    let spend = _spend(client, req).await?;
+    let spend = _spend(&state, client, req).await?;
     Ok(Json(spend))
 }
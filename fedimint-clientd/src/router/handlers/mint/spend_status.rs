@@ -0,0 +1,74 @@
+use anyhow::anyhow;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::Json;
+use multimint::fedimint_client::ClientHandleArc;
+use multimint::fedimint_core::config::FederationId;
+use multimint::fedimint_core::core::OperationId;
+use multimint::fedimint_core::Amount;
+use multimint::fedimint_mint_client::{MintClientModule, MintOperationMeta, SpendOOBState};
+use multimint::operations::latest_update;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+use super::{get_spend_operation, SpendStatus};
+use crate::error::AppError;
+use crate::state::AppState;
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpendStatusRequest {
+    pub operation_id: OperationId,
+    pub federation_id: Option<FederationId>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SpendStatusResponse {
+    pub operation_id: OperationId,
+    pub amount_msat: Amount,
+    pub status: SpendStatus,
+    /// Latest state of the spend's state machine
+    pub state: SpendOOBState,
+}
+
+async fn _spend_status(
+    client: ClientHandleArc,
+    req: SpendStatusRequest,
+) -> Result<SpendStatusResponse, AppError> {
+    let entry = get_spend_operation(&client, req.operation_id).await?;
+    let mint = client.get_first_module::<MintClientModule>();
+    let state = latest_update(
+        mint.subscribe_spend_notes(req.operation_id)
+            .await?
+            .into_stream(),
+    )
+    .await
+    .unwrap_or(SpendOOBState::Created);
+
+    Ok(SpendStatusResponse {
+        operation_id: req.operation_id,
+        amount_msat: entry.meta::<MintOperationMeta>().amount,
+        status: SpendStatus::from(&state),
+        state,
+    })
+}
+
+pub async fn handle_ws(state: AppState, v: Value) -> Result<Value, AppError> {
+    let v = serde_json::from_value::<SpendStatusRequest>(v)
+        .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, anyhow!("Invalid request: {}", e)))?;
+    let client = state.get_client(v.federation_id).await?;
+    let status = _spend_status(client, v).await?;
+    let status_json = json!(status);
+    Ok(status_json)
+}
+
+#[axum_macros::debug_handler]
+pub async fn handle_rest(
+    State(state): State<AppState>,
+    Json(req): Json<SpendStatusRequest>,
+) -> Result<Json<SpendStatusResponse>, AppError> {
+    let client = state.get_client(req.federation_id).await?;
+    let status = _spend_status(client, req).await?;
+    Ok(Json(status))
+}
@@ -17,7 +17,7 @@ use crate::state::AppState;
 #[derive(Debug, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct DepositAddressRequest {
-    pub federation_id: FederationId,
+    pub federation_id: Option<FederationId>,
 }
 
 #[derive(Debug, Serialize)]
@@ -22,7 +22,7 @@ use crate::state::AppState;
 pub struct WithdrawRequest {
     pub address: Address<NetworkUnchecked>,
     pub amount_sat: BitcoinAmountOrAll,
-    pub federation_id: FederationId,
+    pub federation_id: Option<FederationId>,
 }
 
 #[derive(Debug, Serialize)]
@@ -33,6 +33,7 @@ pub struct WithdrawResponse {
 }
 
 async fn _withdraw(
+    state: &AppState,
     client: ClientHandleArc,
     req: WithdrawRequest,
 ) -> Result<WithdrawResponse, AppError> {
@@ -66,6 +67,9 @@ async fn _withdraw(
         ),
     };
     let absolute_fees = fees.amount();
+    state
+        .limits
+        .check_send(multimint::fedimint_core::Amount::from_sats(amount.to_sat()))?;
 
     info!("Attempting withdraw with fees: {fees:?}");
 
@@ -84,7 +88,7 @@ async fn _withdraw(
         match update {
             WithdrawState::Succeeded(txid) => {
                 return Ok(WithdrawResponse {
-                    txid: txid,
+                    txid,
                     fees_sat: absolute_fees.to_sat(),
                 });
             }
@@ -108,7 +112,7 @@ pub async fn handle_ws(state: AppState, v: Value) -> Result<Value, AppError> {
     let v = serde_json::from_value::<WithdrawRequest>(v)
         .map_err(|e| AppError::new(StatusCode::BAD_REQUEST, anyhow!("Invalid request: {}", e)))?;
     let client = state.get_client(v.federation_id).await?;
-    let withdraw = _withdraw(client, v).await?;
+    let withdraw = _withdraw(&state, client, v).await?;
     let withdraw_json = json!(withdraw);
     Ok(withdraw_json)
 }
@@ -119,6 +123,6 @@ pub async fn handle_rest(
     Json(req): Json<WithdrawRequest>,
 ) -> Result<Json<WithdrawResponse>, AppError> {
     let client = state.get_client(req.federation_id).await?;
-    let withdraw = _withdraw(client, req).await?;
+    let withdraw = _withdraw(&state, client, req).await?;
     Ok(Json(withdraw))
 }
@@ -1,5 +1,6 @@
 use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
 use axum::extract::State;
+use axum::http::StatusCode;
 use axum::response::IntoResponse;
 use futures_util::stream::StreamExt;
 use serde::{Deserialize, Serialize};
@@ -54,15 +55,23 @@ pub enum JsonRpcMethod {
     AdminConfig,
     AdminDiscoverVersion,
     AdminFederationIds,
+    AdminFederationStatus,
     AdminInfo,
     AdminJoin,
+    AdminMeta,
     AdminModule,
+    AdminPreview,
+    AdminRecoveryStatus,
     AdminRestore,
+    AdminExportTransactions,
     AdminListOperations,
+    AdminOperation,
     MintDecodeNotes,
     MintEncodeNotes,
     MintReissue,
     MintSpend,
+    MintSpendStatus,
+    MintReclaim,
     MintValidate,
     MintSplit,
     MintCombine,
@@ -72,9 +81,17 @@ pub enum JsonRpcMethod {
     LnClaimExternalReceiveTweaked,
     LnPay,
     LnListGateways,
+    LnRefreshGateways,
     WalletDepositAddress,
     WalletAwaitDeposit,
     WalletWithdraw,
+    AccountsCreate,
+    AccountsList,
+    AccountsBalance,
+    AccountsHistory,
+    AccountsInvoice,
+    AccountsPay,
+    AccountsTransfer,
 }
 
 async fn handle_socket(mut socket: WebSocket, state: AppState) -> Result<(), anyhow::Error> {
@@ -89,6 +106,14 @@ async fn handle_socket(mut socket: WebSocket, state: AppState) -> Result<(), any
                 }
             };
 
+            let Some(_guard) = state.active_requests.begin() else {
+                let res = Err(AppError::new(
+                    StatusCode::SERVICE_UNAVAILABLE,
+                    anyhow::anyhow!("Server is shutting down"),
+                ));
+                socket.send(create_json_rpc_response(res, req.id)?).await?;
+                break;
+            };
             let res = match_method(req.clone(), state.clone()).await;
 
             let res_msg = create_json_rpc_response(res, req.id)?;
@@ -166,6 +191,9 @@ async fn match_method(req: JsonRpcRequest, state: AppState) -> Result<Value, App
             handlers::admin::backup::handle_ws(state.clone(), req.params).await
         }
         JsonRpcMethod::AdminConfig => handlers::admin::config::handle_ws(state.clone()).await,
+        JsonRpcMethod::AdminFederationStatus => {
+            handlers::admin::federation_status::handle_ws(state.clone()).await
+        }
         JsonRpcMethod::AdminDiscoverVersion => {
             handlers::admin::discover_version::handle_ws(state.clone()).await
         }
@@ -175,18 +203,33 @@ async fn match_method(req: JsonRpcRequest, state: AppState) -> Result<Value, App
         JsonRpcMethod::AdminInfo => {
             handlers::admin::info::handle_ws(state.clone(), req.params).await
         }
+        JsonRpcMethod::AdminMeta => {
+            handlers::admin::meta::handle_ws(state.clone(), req.params).await
+        }
         JsonRpcMethod::AdminJoin => {
             handlers::admin::join::handle_ws(state.clone(), req.params).await
         }
+        JsonRpcMethod::AdminPreview => {
+            handlers::admin::preview::handle_ws(state.clone(), req.params).await
+        }
+        JsonRpcMethod::AdminRecoveryStatus => {
+            handlers::admin::recovery_status::handle_ws(state.clone(), req.params).await
+        }
         JsonRpcMethod::AdminModule => {
             handlers::admin::module::handle_ws(state.clone(), req.params).await
         }
         JsonRpcMethod::AdminRestore => {
             handlers::admin::restore::handle_ws(state.clone(), req.params).await
         }
+        JsonRpcMethod::AdminExportTransactions => {
+            handlers::admin::export_transactions::handle_ws(state.clone(), req.params).await
+        }
         JsonRpcMethod::AdminListOperations => {
             handlers::admin::list_operations::handle_ws(state.clone(), req.params).await
         }
+        JsonRpcMethod::AdminOperation => {
+            handlers::admin::operation::handle_ws(state.clone(), req.params).await
+        }
         JsonRpcMethod::MintDecodeNotes => handlers::mint::decode_notes::handle_ws(req.params).await,
         JsonRpcMethod::MintEncodeNotes => handlers::mint::encode_notes::handle_ws(req.params).await,
         JsonRpcMethod::MintReissue => {
@@ -195,6 +238,12 @@ async fn match_method(req: JsonRpcRequest, state: AppState) -> Result<Value, App
         JsonRpcMethod::MintSpend => {
             handlers::mint::spend::handle_ws(state.clone(), req.params).await
         }
+        JsonRpcMethod::MintSpendStatus => {
+            handlers::mint::spend_status::handle_ws(state.clone(), req.params).await
+        }
+        JsonRpcMethod::MintReclaim => {
+            handlers::mint::reclaim::handle_ws(state.clone(), req.params).await
+        }
         JsonRpcMethod::MintValidate => {
             handlers::mint::validate::handle_ws(state.clone(), req.params).await
         }
@@ -217,6 +266,9 @@ async fn match_method(req: JsonRpcRequest, state: AppState) -> Result<Value, App
         JsonRpcMethod::LnListGateways => {
             handlers::ln::list_gateways::handle_ws(state.clone(), req.params).await
         }
+        JsonRpcMethod::LnRefreshGateways => {
+            handlers::ln::refresh_gateways::handle_ws(state.clone(), req.params).await
+        }
         JsonRpcMethod::WalletDepositAddress => {
             handlers::onchain::deposit_address::handle_ws(state.clone(), req.params).await
         }
@@ -226,5 +278,24 @@ async fn match_method(req: JsonRpcRequest, state: AppState) -> Result<Value, App
         JsonRpcMethod::WalletWithdraw => {
             handlers::onchain::withdraw::handle_ws(state.clone(), req.params).await
         }
+        JsonRpcMethod::AccountsCreate => {
+            handlers::accounts::create::handle_ws(state.clone(), req.params).await
+        }
+        JsonRpcMethod::AccountsList => handlers::accounts::list::handle_ws(state.clone()).await,
+        JsonRpcMethod::AccountsBalance => {
+            handlers::accounts::balance::handle_ws(state.clone(), req.params).await
+        }
+        JsonRpcMethod::AccountsHistory => {
+            handlers::accounts::history::handle_ws(state.clone(), req.params).await
+        }
+        JsonRpcMethod::AccountsInvoice => {
+            handlers::accounts::invoice::handle_ws(state.clone(), req.params).await
+        }
+        JsonRpcMethod::AccountsPay => {
+            handlers::accounts::pay::handle_ws(state.clone(), req.params).await
+        }
+        JsonRpcMethod::AccountsTransfer => {
+            handlers::accounts::transfer::handle_ws(state.clone(), req.params).await
+        }
     }
 }
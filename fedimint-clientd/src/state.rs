@@ -1,35 +1,143 @@
 use std::path::PathBuf;
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use anyhow::{anyhow, Result};
 use axum::http::StatusCode;
 use multimint::fedimint_client::ClientHandleArc;
 use multimint::fedimint_core::config::{FederationId, FederationIdPrefix};
-use multimint::MultiMint;
+use multimint::{MultiMint, MultiMintBuilder};
+use tracing::{debug, info, warn};
 
+use crate::config::{Config, GatewayPolicy, Limits};
 use crate::error::AppError;
+use crate::meta::MetaCache;
 #[derive(Debug, Clone)]
 pub struct AppState {
     pub multimint: MultiMint,
+    pub active_requests: ActiveRequests,
+    pub gateway_policy: GatewayPolicy,
+    pub limits: Limits,
+    pub meta_cache: MetaCache,
 }
 
 impl AppState {
-    pub async fn new(fm_db_path: PathBuf) -> Result<Self> {
-        let clients = MultiMint::new(fm_db_path).await?;
-        clients.update_gateway_caches().await?;
-        Ok(Self { multimint: clients })
+    pub async fn new(config: &Config, passphrase: Option<String>) -> Result<Self> {
+        let clients =
+            open_multimint(MultiMint::builder(), config.db_path.clone(), passphrase).await?;
+        Ok(Self::with_multimint(
+            clients,
+            config.gateway_policy.clone(),
+            config.limits.clone(),
+        ))
+    }
+
+    /// Creates the state around an already opened multimint, e.g. one from
+    /// `MultiMint::new_in_memory` in tests.
+    pub fn with_multimint(
+        multimint: MultiMint,
+        gateway_policy: GatewayPolicy,
+        limits: Limits,
+    ) -> Self {
+        Self {
+            multimint,
+            active_requests: ActiveRequests::default(),
+            gateway_policy,
+            limits,
+            meta_cache: MetaCache::default(),
+        }
+    }
+
+    /// Creates the state of a tenant, with its own multimint in `db_path` and
+    /// the same policies as this state. Its requests count towards this
+    /// state's active requests.
+    pub async fn new_tenant(&self, db_path: PathBuf, passphrase: Option<String>) -> Result<Self> {
+        std::fs::create_dir_all(&db_path)?;
+        // Tenants must not share the default wallet's seed
+        let builder = MultiMint::builder().without_env_mnemonic();
+        let clients = open_multimint(builder, db_path, passphrase).await?;
+        Ok(Self {
+            multimint: clients,
+            active_requests: self.active_requests.clone(),
+            gateway_policy: self.gateway_policy.clone(),
+            limits: self.limits.clone(),
+            meta_cache: MetaCache::default(),
+        })
+    }
+
+    /// Stops accepting new requests and waits up to `timeout` for the ones
+    /// in flight to complete. Returns `false` if the timeout was hit.
+    pub async fn wait_for_active_requests(&self, timeout: Duration) -> bool {
+        self.active_requests.close();
+        let deadline = Instant::now() + timeout;
+        loop {
+            let active = self.active_requests.count();
+            if active == 0 {
+                return true;
+            }
+            if Instant::now() >= deadline {
+                warn!("Timed out waiting for {active} requests to complete");
+                return false;
+            }
+            debug!("Waiting for {active} requests to complete...");
+            tokio::time::sleep(Duration::from_millis(250)).await;
+        }
+    }
+
+    /// Shuts down every client so their executors stop after persisting
+    /// their state.
+    pub async fn shutdown(&self) {
+        info!("Shutting down clients...");
+        self.multimint.shutdown().await;
+        info!("All clients shut down.");
     }
 
     // Helper function to get a specific client from the state or default
     pub async fn get_client(
         &self,
-        federation_id: FederationId,
+        federation_id: Option<FederationId>,
     ) -> Result<ClientHandleArc, AppError> {
-        match self.multimint.get(&federation_id).await {
+        let client = self.get_client_even_if_recovering(federation_id).await?;
+        if self.multimint.is_recovering(&client.federation_id()).await {
+            return Err(AppError::new(
+                StatusCode::SERVICE_UNAVAILABLE,
+                anyhow!("Federation is still recovering, see /v2/admin/recovery-status"),
+            ));
+        }
+        Ok(client)
+    }
+
+    /// Like `get_client`, but also returns clients that are still recovering,
+    /// for endpoints reporting on the recovery.
+    pub async fn get_client_even_if_recovering(
+        &self,
+        federation_id: Option<FederationId>,
+    ) -> Result<ClientHandleArc, AppError> {
+        match self.multimint.get_or_default(federation_id).await {
             Some(client) => Ok(client),
-            None => Err(AppError::new(
-                StatusCode::BAD_REQUEST,
-                anyhow!("No client found for federation id"),
-            )),
+            None => {
+                let federation_id = match federation_id {
+                    Some(federation_id) => Some(federation_id),
+                    None => self.multimint.default_federation().await,
+                };
+                match federation_id {
+                    Some(federation_id) if self.multimint.is_loading(&federation_id).await => {
+                        Err(AppError::new(
+                            StatusCode::SERVICE_UNAVAILABLE,
+                            anyhow!("Federation is still loading, see /health/ready"),
+                        ))
+                    }
+                    Some(_) => Err(AppError::new(
+                        StatusCode::BAD_REQUEST,
+                        anyhow!("No client found for federation id"),
+                    )),
+                    None => Err(AppError::new(
+                        StatusCode::BAD_REQUEST,
+                        anyhow!("No federation id provided and no default federation set"),
+                    )),
+                }
+            }
         }
     }
 
@@ -48,3 +156,51 @@ impl AppState {
         }
     }
 }
+
+async fn open_multimint(
+    mut builder: MultiMintBuilder,
+    db_path: PathBuf,
+    passphrase: Option<String>,
+) -> Result<MultiMint> {
+    if let Some(passphrase) = passphrase {
+        builder = builder.with_passphrase(passphrase);
+    }
+    // Clients are opened in the background, see `get_client`
+    builder.build(db_path).await
+}
+
+/// Counts the requests currently being handled so a shutdown can wait for
+/// them to complete before shutting down the clients.
+#[derive(Debug, Clone, Default)]
+pub struct ActiveRequests {
+    count: Arc<AtomicUsize>,
+    closed: Arc<AtomicBool>,
+}
+
+impl ActiveRequests {
+    /// Registers a new request, returns `None` once shutting down. The request
+    /// counts as active until the returned guard is dropped.
+    pub fn begin(&self) -> Option<ActiveRequestGuard> {
+        if self.closed.load(Ordering::SeqCst) {
+            return None;
+        }
+        self.count.fetch_add(1, Ordering::SeqCst);
+        Some(ActiveRequestGuard(self.count.clone()))
+    }
+
+    pub fn close(&self) {
+        self.closed.store(true, Ordering::SeqCst);
+    }
+
+    pub fn count(&self) -> usize {
+        self.count.load(Ordering::SeqCst)
+    }
+}
+
+pub struct ActiveRequestGuard(Arc<AtomicUsize>);
+
+impl Drop for ActiveRequestGuard {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::SeqCst);
+    }
+}
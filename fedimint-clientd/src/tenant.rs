@@ -0,0 +1,85 @@
+//! Independent wallets (tenants) served by one process.
+//!
+//! Every tenant has its own multimint, and so its own mnemonic, federations,
+//! balances and history, in `<db_path>/tenants/<name>`. Requests reach a tenant
+//! under `/tenants/<name>/v2/...` (or `/tenants/<name>/ws`), or under the usual
+//! paths when they're authenticated with one of the tenant's api keys. Tenant
+//! api keys only give access to their own tenant, the main password and api
+//! keys give access to the default wallet and every tenant.
+
+use std::sync::Arc;
+
+use axum::extract::{Request, State};
+use axum::http::uri::PathAndQuery;
+use axum::http::{header, HeaderValue, StatusCode, Uri};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+
+use crate::config::TenantConfig;
+
+/// Maps the bearer tokens of the tenants to the tenant they belong to.
+#[derive(Debug, Clone, Default)]
+pub struct TenantKeys {
+    header_values: Arc<Vec<(HeaderValue, String)>>,
+}
+
+impl TenantKeys {
+    pub fn new(tenants: &[TenantConfig]) -> anyhow::Result<Self> {
+        let mut header_values = Vec::new();
+        for tenant in tenants {
+            for key in &tenant.api_keys {
+                let header_value = HeaderValue::from_str(&format!("Bearer {key}"))
+                    .map_err(|e| anyhow::anyhow!("Invalid api key: {e}"))?;
+                header_values.push((header_value, tenant.name.clone()));
+            }
+        }
+
+        Ok(Self {
+            header_values: Arc::new(header_values),
+        })
+    }
+
+    fn tenant(&self, authorization: &HeaderValue) -> Option<&str> {
+        self.header_values
+            .iter()
+            .find(|(header_value, _)| header_value == authorization)
+            .map(|(_, tenant)| tenant.as_str())
+    }
+}
+
+/// Sends requests authenticated with a tenant's api key to that tenant, and
+/// rejects them for other tenants. Must run before routing.
+pub async fn route_tenant(
+    State(tenant_keys): State<TenantKeys>,
+    mut req: Request,
+    next: Next,
+) -> Response {
+    let tenant = req
+        .headers()
+        .get(header::AUTHORIZATION)
+        .and_then(|authorization| tenant_keys.tenant(authorization))
+        .map(str::to_owned);
+    let Some(tenant) = tenant else {
+        return next.run(req).await;
+    };
+
+    let path = req.uri().path();
+    if let Some(rest) = path.strip_prefix("/tenants/") {
+        if rest.split('/').next() != Some(tenant.as_str()) {
+            return (StatusCode::FORBIDDEN, "Api key is for another tenant").into_response();
+        }
+    } else if path.starts_with("/v2") || path == "/ws" {
+        let path_and_query = req
+            .uri()
+            .path_and_query()
+            .map_or(path, PathAndQuery::as_str);
+        let mut parts = req.uri().clone().into_parts();
+        parts.path_and_query = format!("/tenants/{tenant}{path_and_query}").parse().ok();
+        match Uri::from_parts(parts) {
+            Ok(uri) => *req.uri_mut() = uri,
+            Err(_) => return StatusCode::BAD_REQUEST.into_response(),
+        }
+    }
+
+    next.run(req).await
+}
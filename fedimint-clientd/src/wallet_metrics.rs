@@ -0,0 +1,280 @@
+//! Prometheus metrics about the wallets of the federations, as opposed to the
+//! HTTP metrics recorded per request by `track_metrics`.
+//!
+//! A background task periodically records:
+//! - `fedimint_ecash_balance_msat{federation_id}`
+//! - `fedimint_notes{federation_id, denomination_msat}`
+//! - `fedimint_gateways{federation_id}`
+//! - `fedimint_pending_operations{federation_id, kind}`
+//! - `fedimint_payments_total{federation_id, kind, status}`
+//! - `fedimint_payment_fees_msat_total{federation_id, kind}`
+//!
+//! The metrics of a tenant's wallet also carry a `tenant` label.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::time::{Duration, SystemTime};
+
+use multimint::fedimint_client::oplog::OperationLogEntry;
+use multimint::fedimint_client::ClientHandleArc;
+use multimint::fedimint_core::config::FederationId;
+use multimint::fedimint_core::core::OperationId;
+use multimint::fedimint_core::Amount;
+use multimint::fedimint_ln_client::{
+    LightningClientModule, LightningOperationMeta, LightningOperationMetaVariant,
+};
+use multimint::fedimint_wallet_client::{WalletOperationMeta, WalletOperationMetaVariant};
+use multimint::operations::PAGE_SIZE;
+use serde_json::Value;
+use tracing::debug;
+
+use crate::router::handlers::admin::operation::operation_amounts;
+use crate::router::handlers::admin::OperationStatus;
+use crate::state::AppState;
+
+/// Operations still without an outcome this long after their creation stop
+/// counting as pending, e.g. unfunded deposit addresses or invoices and spends
+/// that were never awaited
+const PENDING_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Operation log progress of one federation, so each update only reads the
+/// operations that are new or were still pending.
+#[derive(Debug, Default)]
+struct OperationTracker {
+    /// Creation time of the newest operation seen
+    newest: Option<SystemTime>,
+    /// Recent operations without an outcome yet, with their module kind and
+    /// creation time
+    pending: HashMap<OperationId, (String, SystemTime)>,
+    /// Module kinds of all operations seen, to report zero pending operations
+    /// for kinds that have none
+    kinds: BTreeSet<String>,
+    /// Completed payments by payment kind and status
+    payments: BTreeMap<(&'static str, OperationStatus), u64>,
+    /// Fees of succeeded payments by payment kind
+    fees_msat: BTreeMap<&'static str, u64>,
+    /// Denominations reported in `fedimint_notes`, to reset the ones we run out
+    /// of
+    denominations: BTreeSet<Amount>,
+}
+
+impl OperationTracker {
+    /// Reads the operations created since the last update and checks the
+    /// pending ones for an outcome. Operations older than `PENDING_WINDOW`
+    /// are only counted if they already have an outcome.
+    async fn update(&mut self, client: &ClientHandleArc) {
+        let window_start = SystemTime::now()
+            .checked_sub(PENDING_WINDOW)
+            .unwrap_or(SystemTime::UNIX_EPOCH);
+        self.pending
+            .retain(|_, (_, creation_time)| *creation_time >= window_start);
+
+        let mut cursor = None;
+        let mut newest = self.newest;
+        'pages: loop {
+            let page = client
+                .operation_log()
+                .list_operations(PAGE_SIZE, cursor)
+                .await;
+            let exhausted = page.len() < PAGE_SIZE;
+
+            for (key, entry) in page {
+                cursor = Some(key);
+                if self.newest.is_some_and(|seen| key.creation_time <= seen) {
+                    break 'pages;
+                }
+                newest = newest.max(Some(key.creation_time));
+                self.kinds.insert(entry.operation_module_kind().to_owned());
+                match entry.outcome::<Value>() {
+                    Some(outcome) => self.record_outcome(&entry, &outcome),
+                    None if key.creation_time >= window_start => {
+                        self.pending.insert(
+                            key.operation_id,
+                            (entry.operation_module_kind().to_owned(), key.creation_time),
+                        );
+                    }
+                    None => {}
+                }
+            }
+
+            if exhausted {
+                break;
+            }
+        }
+        self.newest = newest;
+
+        let mut completed = Vec::new();
+        for operation_id in self.pending.keys() {
+            let Some(entry) = client.operation_log().get_operation(*operation_id).await else {
+                continue;
+            };
+            let Some(outcome) = entry.outcome::<Value>() else {
+                continue;
+            };
+            completed.push((*operation_id, entry, outcome));
+        }
+        for (operation_id, entry, outcome) in completed {
+            self.pending.remove(&operation_id);
+            self.record_outcome(&entry, &outcome);
+        }
+    }
+
+    /// Counts a completed operation if it's a payment.
+    fn record_outcome(&mut self, entry: &OperationLogEntry, outcome: &Value) {
+        let Some(kind) = payment_kind(entry) else {
+            return;
+        };
+        let status = OperationStatus::from_state(entry, Some(outcome));
+        *self.payments.entry((kind, status)).or_default() += 1;
+        if status == OperationStatus::Succeeded {
+            let (_, fee) = operation_amounts(entry);
+            *self.fees_msat.entry(kind).or_default() += fee.map_or(0, |fee| fee.msats);
+        }
+    }
+
+    /// Number of pending operations of every module kind seen.
+    fn pending_by_kind(&self) -> BTreeMap<&str, usize> {
+        let mut pending = self
+            .kinds
+            .iter()
+            .map(|kind| (kind.as_str(), 0))
+            .collect::<BTreeMap<_, _>>();
+        for (kind, _) in self.pending.values() {
+            *pending.entry(kind.as_str()).or_default() += 1;
+        }
+        pending
+    }
+}
+
+/// Kind of outgoing payment counted in `fedimint_payments_total`, if any.
+fn payment_kind(entry: &OperationLogEntry) -> Option<&'static str> {
+    match entry.operation_module_kind() {
+        "ln" => match entry.meta::<LightningOperationMeta>().variant {
+            LightningOperationMetaVariant::Pay(_) => Some("ln_pay"),
+            _ => None,
+        },
+        #[cfg(feature = "lnv2")]
+        "lnv2" => {
+            use multimint::fedimint_lnv2_client as lnv2;
+
+            match entry.meta::<lnv2::LightningOperationMeta>() {
+                lnv2::LightningOperationMeta::Send(_) => Some("lnv2_send"),
+                lnv2::LightningOperationMeta::Receive(_) => None,
+            }
+        }
+        "wallet" => match entry.meta::<WalletOperationMeta>().variant {
+            WalletOperationMetaVariant::Withdraw { .. } => Some("wallet_withdraw"),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+type Labels = Vec<(&'static str, String)>;
+
+/// `labels` with another label added.
+fn with_label(labels: &Labels, key: &'static str, value: impl Into<String>) -> Labels {
+    let mut labels = labels.clone();
+    labels.push((key, value.into()));
+    labels
+}
+
+async fn record_federation(
+    tenant: Option<&str>,
+    federation_id: FederationId,
+    client: &ClientHandleArc,
+    tracker: &mut OperationTracker,
+) {
+    let mut labels: Labels = vec![("federation_id", federation_id.to_string())];
+    if let Some(tenant) = tenant {
+        labels.push(("tenant", tenant.to_owned()));
+    }
+
+    let balance = client.get_balance().await;
+    metrics::gauge!("fedimint_ecash_balance_msat", labels.as_slice()).set(balance.msats as f64);
+
+    let summary = multimint::info::note_summary(client).await;
+    let mut denominations = BTreeSet::new();
+    for (denomination, count) in summary.iter() {
+        let labels = with_label(&labels, "denomination_msat", denomination.msats.to_string());
+        metrics::gauge!("fedimint_notes", labels.as_slice()).set(count as f64);
+        denominations.insert(denomination);
+    }
+    for denomination in tracker.denominations.difference(&denominations) {
+        let labels = with_label(&labels, "denomination_msat", denomination.msats.to_string());
+        metrics::gauge!("fedimint_notes", labels.as_slice()).set(0.0);
+    }
+    tracker.denominations = denominations;
+
+    if client
+        .get_first_instance(&multimint::fedimint_ln_common::KIND)
+        .is_some()
+    {
+        let gateways = client
+            .get_first_module::<LightningClientModule>()
+            .list_gateways()
+            .await;
+        metrics::gauge!("fedimint_gateways", labels.as_slice()).set(gateways.len() as f64);
+    }
+
+    tracker.update(client).await;
+
+    for (kind, pending) in tracker.pending_by_kind() {
+        let labels = with_label(&labels, "kind", kind);
+        metrics::gauge!("fedimint_pending_operations", labels.as_slice()).set(pending as f64);
+    }
+
+    for ((kind, status), count) in &tracker.payments {
+        let status = match status {
+            OperationStatus::Pending => "pending",
+            OperationStatus::Succeeded => "succeeded",
+            OperationStatus::Failed => "failed",
+            OperationStatus::Refunded => "refunded",
+        };
+        let labels = with_label(&with_label(&labels, "kind", *kind), "status", status);
+        metrics::counter!("fedimint_payments_total", labels.as_slice()).absolute(*count);
+    }
+
+    for (kind, fees_msat) in &tracker.fees_msat {
+        let labels = with_label(&labels, "kind", *kind);
+        metrics::counter!("fedimint_payment_fees_msat_total", labels.as_slice())
+            .absolute(*fees_msat);
+    }
+}
+
+/// Spawns the task updating the metrics of the default wallet `state` and of
+/// the `tenants` every `interval`.
+///
+/// Operations only count as completed once their outcome is stored in the
+/// operation log, which happens when their updates are awaited. Payments
+/// made before startup are counted on the first update, operations only count
+/// as pending during `PENDING_WINDOW`.
+pub fn spawn_wallet_metrics_task(
+    state: AppState,
+    tenants: Vec<(String, AppState)>,
+    interval: Duration,
+) {
+    tokio::spawn(async move {
+        let wallets = std::iter::once((None, state))
+            .chain(tenants.into_iter().map(|(name, state)| (Some(name), state)))
+            .collect::<Vec<_>>();
+        let mut trackers: HashMap<(Option<String>, FederationId), OperationTracker> =
+            HashMap::new();
+        let mut ticker = tokio::time::interval(interval);
+        ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+        loop {
+            ticker.tick().await;
+            debug!("Updating wallet metrics");
+            for (tenant, state) in &wallets {
+                for client in state.multimint.all().await {
+                    let federation_id = client.federation_id();
+                    if state.multimint.is_recovering(&federation_id).await {
+                        continue;
+                    }
+                    let tracker = trackers.entry((tenant.clone(), federation_id)).or_default();
+                    record_federation(tenant.as_deref(), federation_id, &client, tracker).await;
+                }
+            }
+        }
+    });
+}
@@ -38,7 +38,7 @@ impl Invoice {
         hex::encode(self.invoice.payment_hash())
     }
 
-    pub fn description(&self) -> Option<Bolt11InvoiceDescription> {
+    pub fn description(&self) -> Option<Bolt11InvoiceDescription<'_>> {
         Some(self.invoice.description())
     }
 }
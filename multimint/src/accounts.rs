@@ -0,0 +1,551 @@
+//! Internal accounts sharing the multimint's balance.
+//!
+//! Accounts are a ledger kept in the multimint database, they don't hold funds
+//! of their own: deposits credit an account for funds received by one of the
+//! clients, payments debit it for funds sent, and transfers between accounts
+//! only update the ledger. Every change is recorded in the account's history.
+//!
+//! The ledger is kept under `ACCOUNTS_DB_PREFIX`, apart from the clients' data.
+
+use anyhow::{anyhow, bail, Result};
+use fedimint_core::config::FederationId;
+use fedimint_core::core::OperationId;
+use fedimint_core::db::{Database, DatabaseTransaction, IDatabaseTransactionOpsCoreTyped};
+use fedimint_core::Amount;
+use futures_util::StreamExt;
+
+use crate::db::{
+    Account, AccountEntry, AccountEntryKey, AccountEntryKeyPrefix, AccountEntryKind, AccountKey,
+    AccountKeyPrefix, AccountOperation, AccountOperationKey, AccountOperationKeyPrefix,
+    AccountPaymentKey, AccountPaymentKeyPrefix, ACCOUNTS_DB_PREFIX,
+};
+
+/// Details of a ledger entry, besides its amount and kind
+#[derive(Debug, Clone, Default)]
+pub struct EntryDetails {
+    pub federation_id: Option<FederationId>,
+    pub operation_id: Option<OperationId>,
+    pub description: Option<String>,
+}
+
+/// Handle to the accounts stored in a multimint's database.
+#[derive(Debug, Clone)]
+pub struct Accounts {
+    db: Database,
+}
+
+impl Accounts {
+    /// Accounts kept in the multimint database `db`.
+    pub(crate) fn new(db: &Database) -> Self {
+        Self {
+            db: db.with_prefix(ACCOUNTS_DB_PREFIX.to_vec()),
+        }
+    }
+
+    /// Create an account with a zero balance.
+    pub async fn create(&self, name: &str) -> Result<Account> {
+        let mut dbtx = self.db.begin_transaction().await;
+        let key = AccountKey {
+            name: name.to_string(),
+        };
+        if dbtx.get_value(&key).await.is_some() {
+            bail!("Account {name} already exists");
+        }
+        let account = Account {
+            balance: Amount::ZERO,
+            created_at: now(),
+            next_entry: 0,
+        };
+        dbtx.insert_entry(&key, &account).await;
+        commit(dbtx).await?;
+        Ok(account)
+    }
+
+    pub async fn get(&self, name: &str) -> Option<Account> {
+        self.db
+            .begin_transaction_nc()
+            .await
+            .get_value(&AccountKey {
+                name: name.to_string(),
+            })
+            .await
+    }
+
+    pub async fn list(&self) -> Vec<(String, Account)> {
+        self.db
+            .begin_transaction_nc()
+            .await
+            .find_by_prefix(&AccountKeyPrefix)
+            .await
+            .map(|(key, account)| (key.name, account))
+            .collect()
+            .await
+    }
+
+    /// Get the history of an account, most recent entries first.
+    pub async fn history(&self, name: &str, limit: Option<usize>) -> Result<Vec<AccountEntry>> {
+        if self.get(name).await.is_none() {
+            bail!("Account {name} not found");
+        }
+        let mut entries = self
+            .db
+            .begin_transaction_nc()
+            .await
+            .find_by_prefix(&AccountEntryKeyPrefix {
+                account: name.to_string(),
+            })
+            .await
+            .collect::<Vec<_>>()
+            .await;
+        entries.sort_by_key(|(key, _)| std::cmp::Reverse(key.index));
+        Ok(entries
+            .into_iter()
+            .take(limit.unwrap_or(usize::MAX))
+            .map(|(_, entry)| entry)
+            .collect())
+    }
+
+    /// Credit or debit an account depending on `kind`. Debits fail if the
+    /// account's balance is too low.
+    pub async fn record(
+        &self,
+        name: &str,
+        amount: Amount,
+        kind: AccountEntryKind,
+        details: EntryDetails,
+    ) -> Result<AccountEntry> {
+        let mut dbtx = self.db.begin_transaction().await;
+        let entry = apply(&mut dbtx.to_ref_nc(), name, amount, kind, details, None).await?;
+        commit(dbtx).await?;
+        Ok(entry)
+    }
+
+    /// Move `amount` from one account to another, without touching the
+    /// federations. Returns the entries of both accounts.
+    pub async fn transfer(
+        &self,
+        from: &str,
+        to: &str,
+        amount: Amount,
+        description: Option<String>,
+    ) -> Result<(AccountEntry, AccountEntry)> {
+        if from == to {
+            bail!("Can't transfer to the same account");
+        }
+        let details = EntryDetails {
+            description,
+            ..Default::default()
+        };
+        let mut dbtx = self.db.begin_transaction().await;
+        let out = apply(
+            &mut dbtx.to_ref_nc(),
+            from,
+            amount,
+            AccountEntryKind::TransferOut,
+            details.clone(),
+            Some(to),
+        )
+        .await?;
+        let incoming = apply(
+            &mut dbtx.to_ref_nc(),
+            to,
+            amount,
+            AccountEntryKind::TransferIn,
+            details,
+            Some(from),
+        )
+        .await?;
+        commit(dbtx).await?;
+        Ok((out, incoming))
+    }
+
+    /// Remember to credit the invoice of `operation_id` to an account once
+    /// it's paid, see `settle_pending_deposit`.
+    pub async fn add_pending_deposit(
+        &self,
+        operation_id: OperationId,
+        deposit: AccountOperation,
+    ) -> Result<()> {
+        let mut dbtx = self.db.begin_transaction().await;
+        dbtx.insert_entry(&AccountOperationKey { operation_id }, &deposit)
+            .await;
+        commit(dbtx).await
+    }
+
+    /// Invoices waiting to be credited to an account.
+    pub async fn pending_deposits(&self) -> Vec<(OperationId, AccountOperation)> {
+        self.db
+            .begin_transaction_nc()
+            .await
+            .find_by_prefix(&AccountOperationKeyPrefix)
+            .await
+            .map(|(key, deposit)| (key.operation_id, deposit))
+            .collect()
+            .await
+    }
+
+    /// Settle a pending deposit: credit its account if `paid`, otherwise
+    /// just forget it. Settling the same deposit twice does nothing.
+    pub async fn settle_pending_deposit(
+        &self,
+        operation_id: OperationId,
+        paid: bool,
+    ) -> Result<Option<AccountEntry>> {
+        let mut dbtx = self.db.begin_transaction().await;
+        let key = AccountOperationKey { operation_id };
+        let Some(deposit) = dbtx.remove_entry(&key).await else {
+            return Ok(None);
+        };
+        let entry = if paid {
+            let details = EntryDetails {
+                federation_id: Some(deposit.federation_id),
+                operation_id: Some(operation_id),
+                description: None,
+            };
+            Some(
+                apply(
+                    &mut dbtx.to_ref_nc(),
+                    &deposit.account,
+                    deposit.amount,
+                    AccountEntryKind::Deposit,
+                    details,
+                    None,
+                )
+                .await?,
+            )
+        } else {
+            None
+        };
+        commit(dbtx).await?;
+        Ok(entry)
+    }
+
+    /// Remember a payment debited from an account until its outcome is known,
+    /// see `settle_pending_payment`. `unused` is the part of the debit that
+    /// turned out not to be needed, e.g. a fee reserve above the actual fee,
+    /// and is refunded right away.
+    pub async fn add_pending_payment(
+        &self,
+        operation_id: OperationId,
+        payment: AccountOperation,
+        unused: Amount,
+    ) -> Result<Option<AccountEntry>> {
+        let mut dbtx = self.db.begin_transaction().await;
+        let refund = if unused == Amount::ZERO {
+            None
+        } else {
+            let details = EntryDetails {
+                federation_id: Some(payment.federation_id),
+                operation_id: Some(operation_id),
+                description: Some("Unused fee reserve".to_string()),
+            };
+            Some(
+                apply(
+                    &mut dbtx.to_ref_nc(),
+                    &payment.account,
+                    unused,
+                    AccountEntryKind::Refund,
+                    details,
+                    None,
+                )
+                .await?,
+            )
+        };
+        dbtx.insert_entry(&AccountPaymentKey { operation_id }, &payment)
+            .await;
+        commit(dbtx).await?;
+        Ok(refund)
+    }
+
+    /// Payments waiting for their outcome.
+    pub async fn pending_payments(&self) -> Vec<(OperationId, AccountOperation)> {
+        self.db
+            .begin_transaction_nc()
+            .await
+            .find_by_prefix(&AccountPaymentKeyPrefix)
+            .await
+            .map(|(key, payment)| (key.operation_id, payment))
+            .collect()
+            .await
+    }
+
+    /// Settle a pending payment once its operation finished: refund its
+    /// account the part of the debit above the `spent` amount, all of it if
+    /// the funds were refunded to the wallet. Settling the same payment twice
+    /// does nothing.
+    pub async fn settle_pending_payment(
+        &self,
+        operation_id: OperationId,
+        spent: Amount,
+    ) -> Result<Option<AccountEntry>> {
+        let mut dbtx = self.db.begin_transaction().await;
+        let key = AccountPaymentKey { operation_id };
+        let Some(payment) = dbtx.remove_entry(&key).await else {
+            return Ok(None);
+        };
+        let refund = payment.amount.saturating_sub(spent);
+        let entry = if refund == Amount::ZERO {
+            None
+        } else {
+            let details = EntryDetails {
+                federation_id: Some(payment.federation_id),
+                operation_id: Some(operation_id),
+                description: (spent != Amount::ZERO).then(|| "Unused fee reserve".to_string()),
+            };
+            Some(
+                apply(
+                    &mut dbtx.to_ref_nc(),
+                    &payment.account,
+                    refund,
+                    AccountEntryKind::Refund,
+                    details,
+                    None,
+                )
+                .await?,
+            )
+        };
+        commit(dbtx).await?;
+        Ok(entry)
+    }
+}
+
+impl AccountEntryKind {
+    pub fn is_credit(self) -> bool {
+        match self {
+            AccountEntryKind::Deposit | AccountEntryKind::Refund | AccountEntryKind::TransferIn => {
+                true
+            }
+            AccountEntryKind::Payment | AccountEntryKind::TransferOut => false,
+        }
+    }
+}
+
+/// Updates the balance of an account and appends the entry to its history.
+async fn apply(
+    dbtx: &mut DatabaseTransaction<'_>,
+    name: &str,
+    amount: Amount,
+    kind: AccountEntryKind,
+    details: EntryDetails,
+    counterparty: Option<&str>,
+) -> Result<AccountEntry> {
+    let key = AccountKey {
+        name: name.to_string(),
+    };
+    let mut account = dbtx
+        .get_value(&key)
+        .await
+        .ok_or_else(|| anyhow!("Account {name} not found"))?;
+
+    account.balance = if kind.is_credit() {
+        account.balance + amount
+    } else {
+        account.balance.checked_sub(amount).ok_or_else(|| {
+            anyhow!(
+                "Insufficient balance in account {name}: {} < {amount}",
+                account.balance
+            )
+        })?
+    };
+
+    let entry = AccountEntry {
+        timestamp: now(),
+        kind,
+        amount,
+        balance: account.balance,
+        federation_id: details.federation_id,
+        operation_id: details.operation_id,
+        counterparty: counterparty.map(str::to_string),
+        description: details.description,
+    };
+    dbtx.insert_entry(
+        &AccountEntryKey {
+            account: name.to_string(),
+            index: account.next_entry,
+        },
+        &entry,
+    )
+    .await;
+    account.next_entry += 1;
+    dbtx.insert_entry(&key, &account).await;
+    Ok(entry)
+}
+
+async fn commit(dbtx: DatabaseTransaction<'_, fedimint_core::db::Committable>) -> Result<()> {
+    dbtx.commit_tx_result()
+        .await
+        .map_err(|e| anyhow!("Failed to update accounts: {e:?}"))
+}
+
+fn now() -> u64 {
+    fedimint_core::time::duration_since_epoch().as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use fedimint_core::db::mem_impl::MemDatabase;
+    use fedimint_core::db::IDatabaseTransactionOpsCore;
+
+    use super::*;
+    use crate::MultiMint;
+
+    async fn accounts_with(balances: &[(&str, u64)]) -> Result<(MultiMint, Accounts)> {
+        let multimint = MultiMint::builder()
+            .without_env_mnemonic()
+            .build_with_database(Database::new(MemDatabase::new(), Default::default()))
+            .await?;
+        let accounts = multimint.accounts();
+        for (name, msats) in balances {
+            accounts.create(name).await?;
+            if *msats > 0 {
+                accounts
+                    .record(
+                        name,
+                        Amount::from_msats(*msats),
+                        AccountEntryKind::Deposit,
+                        EntryDetails::default(),
+                    )
+                    .await?;
+            }
+        }
+        Ok((multimint, accounts))
+    }
+
+    async fn balance(accounts: &Accounts, name: &str) -> Amount {
+        accounts.get(name).await.expect("account exists").balance
+    }
+
+    fn pending(account: &str, msats: u64) -> AccountOperation {
+        AccountOperation {
+            account: account.to_string(),
+            federation_id: FederationId::dummy(),
+            amount: Amount::from_msats(msats),
+        }
+    }
+
+    #[tokio::test]
+    async fn overdraft_is_rejected() -> Result<()> {
+        let (_multimint, accounts) = accounts_with(&[("alice", 1_000)]).await?;
+
+        let overdraft = accounts
+            .record(
+                "alice",
+                Amount::from_msats(1_001),
+                AccountEntryKind::Payment,
+                EntryDetails::default(),
+            )
+            .await;
+        assert!(overdraft.is_err());
+        assert_eq!(balance(&accounts, "alice").await, Amount::from_msats(1_000));
+        assert_eq!(accounts.history("alice", None).await?.len(), 1);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn failed_transfer_changes_neither_account() -> Result<()> {
+        let (_multimint, accounts) = accounts_with(&[("alice", 1_000), ("bob", 0)]).await?;
+
+        // The debit of alice succeeds, the credit of the missing account fails
+        assert!(accounts
+            .transfer("alice", "carol", Amount::from_msats(500), None)
+            .await
+            .is_err());
+        assert!(accounts
+            .transfer("alice", "bob", Amount::from_msats(1_001), None)
+            .await
+            .is_err());
+        assert_eq!(balance(&accounts, "alice").await, Amount::from_msats(1_000));
+        assert_eq!(balance(&accounts, "bob").await, Amount::ZERO);
+        assert_eq!(accounts.history("alice", None).await?.len(), 1);
+        assert!(accounts.history("bob", None).await?.is_empty());
+
+        accounts
+            .transfer("alice", "bob", Amount::from_msats(400), None)
+            .await?;
+        assert_eq!(balance(&accounts, "alice").await, Amount::from_msats(600));
+        assert_eq!(balance(&accounts, "bob").await, Amount::from_msats(400));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn deposits_and_payments_settle_once() -> Result<()> {
+        let (_multimint, accounts) = accounts_with(&[("alice", 1_000)]).await?;
+        let deposit_id = OperationId([1; 32]);
+        let payment_id = OperationId([2; 32]);
+
+        accounts
+            .add_pending_deposit(deposit_id, pending("alice", 300))
+            .await?;
+        assert!(accounts
+            .settle_pending_deposit(deposit_id, true)
+            .await?
+            .is_some());
+        assert!(accounts
+            .settle_pending_deposit(deposit_id, true)
+            .await?
+            .is_none());
+        assert_eq!(balance(&accounts, "alice").await, Amount::from_msats(1_300));
+
+        // A payment of 500 with a fee of 100 debited with a fee reserve of 200
+        accounts
+            .record(
+                "alice",
+                Amount::from_msats(700),
+                AccountEntryKind::Payment,
+                EntryDetails::default(),
+            )
+            .await?;
+        accounts
+            .add_pending_payment(payment_id, pending("alice", 600), Amount::from_msats(100))
+            .await?;
+        assert_eq!(accounts.pending_payments().await.len(), 1);
+        assert!(accounts
+            .settle_pending_payment(payment_id, Amount::ZERO)
+            .await?
+            .is_some());
+        assert!(accounts
+            .settle_pending_payment(payment_id, Amount::ZERO)
+            .await?
+            .is_none());
+        assert!(accounts.pending_payments().await.is_empty());
+        assert_eq!(balance(&accounts, "alice").await, Amount::from_msats(1_300));
+
+        // A payment of 500 whose fee of 50 is only known once it succeeded
+        let payment_id = OperationId([3; 32]);
+        accounts
+            .record(
+                "alice",
+                Amount::from_msats(700),
+                AccountEntryKind::Payment,
+                EntryDetails::default(),
+            )
+            .await?;
+        accounts
+            .add_pending_payment(payment_id, pending("alice", 700), Amount::ZERO)
+            .await?;
+        accounts
+            .settle_pending_payment(payment_id, Amount::from_msats(550))
+            .await?;
+        assert_eq!(balance(&accounts, "alice").await, Amount::from_msats(750));
+        Ok(())
+    }
+
+    /// Clients keep their data under their federation id, which may start
+    /// with any byte.
+    #[tokio::test]
+    async fn ledger_ignores_client_data() -> Result<()> {
+        let (multimint, accounts) = accounts_with(&[("alice", 1_000)]).await?;
+        let mut dbtx = multimint.db.begin_transaction().await;
+        for first_byte in 0..=u8::MAX {
+            dbtx.raw_insert_bytes(&[first_byte, 0xde, 0xad], &[0xbe, 0xef])
+                .await?;
+        }
+        dbtx.commit_tx().await;
+
+        assert_eq!(accounts.list().await.len(), 1);
+        assert_eq!(accounts.history("alice", None).await?.len(), 1);
+        assert!(accounts.pending_payments().await.is_empty());
+        assert!(accounts.pending_deposits().await.is_empty());
+        Ok(())
+    }
+}
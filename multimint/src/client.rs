@@ -13,7 +13,8 @@ use fedimint_client::derivable_secret::{ChildId, DerivableSecret};
 use fedimint_client::module::init::ClientModuleInitRegistry;
 use fedimint_client::secret::RootSecretStrategy;
 use fedimint_client::{Client, ClientBuilder};
-use fedimint_core::config::FederationId;
+use fedimint_core::config::{ClientConfig, FederationId};
+use fedimint_core::core::ModuleInstanceId;
 use fedimint_core::db::{
     Committable, Database, DatabaseTransaction, IDatabaseTransactionOpsCoreTyped,
 };
@@ -22,46 +23,115 @@ use fedimint_ln_client::LightningClientInit;
 use fedimint_mint_client::MintClientInit;
 use fedimint_wallet_client::WalletClientInit;
 use futures_util::StreamExt;
+use tracing::info;
 
 use crate::db::{FederationConfig, FederationIdKey, FederationIdKeyPrefix};
 
+/// Kind of the module used as the client's primary module
+const PRIMARY_MODULE_KIND: &str = "mint";
+
+/// The client modules attached to every client: wallet, mint and lightning,
+/// plus meta and lightning v2 when the `meta` and `lnv2` features are enabled.
+pub fn default_module_inits() -> ClientModuleInitRegistry {
+    let mut registry = ClientModuleInitRegistry::new();
+    registry.attach(WalletClientInit::default());
+    registry.attach(MintClientInit);
+    registry.attach(LightningClientInit::default());
+    #[cfg(feature = "meta")]
+    registry.attach(fedimint_meta_client::MetaClientInit);
+    #[cfg(feature = "lnv2")]
+    registry.attach(fedimint_lnv2_client::LightningClientInit::default());
+    registry
+}
+
 #[derive(Debug, Clone)]
 pub struct LocalClientBuilder {
     mnemonic: Mnemonic,
+    module_inits: ClientModuleInitRegistry,
 }
 
 impl LocalClientBuilder {
     pub fn new(mnemonic: Mnemonic) -> Self {
-        Self { mnemonic }
+        Self::with_module_inits(mnemonic, default_module_inits())
+    }
+
+    /// Create a builder whose clients use the client modules in
+    /// `module_inits`, which should include at least the mint module.
+    pub fn with_module_inits(mnemonic: Mnemonic, module_inits: ClientModuleInitRegistry) -> Self {
+        Self {
+            mnemonic,
+            module_inits,
+        }
     }
 }
 
 impl LocalClientBuilder {
     /// Build a new client with the given config and optional manual secret
+    ///
+    /// Uninitialized clients join the federation, or recover if the
+    /// federation holds a backup for our secret.
     #[allow(clippy::too_many_arguments)]
     pub async fn build(
         &self,
         db: &Database,
         config: FederationConfig,
     ) -> Result<fedimint_client::ClientHandleArc> {
+        let (client, _) = self.join_or_recover(db, config, false).await?;
+        Ok(client)
+    }
+
+    /// Open the client if its database is initialized, otherwise join the
+    /// federation or, if `recover` is set or the federation holds a backup for
+    /// our secret, recover from the federation's history. Returns whether the
+    /// client is recovering.
+    pub async fn join_or_recover(
+        &self,
+        db: &Database,
+        config: FederationConfig,
+        recover: bool,
+    ) -> Result<(fedimint_client::ClientHandleArc, bool)> {
         let federation_id = config.invite_code.federation_id();
         let db = db.with_prefix(federation_id.consensus_encode_to_vec());
         let secret = self.derive_federation_secret(&federation_id);
-        Self::verify_client_config(&db, federation_id).await?;
+        let saved_config = Self::verify_client_config(&db, federation_id).await?;
 
-        let client_builder = self.create_client_builder(db.clone()).await?;
+        if Client::is_initialized(&db).await {
+            let saved_config = saved_config
+                .ok_or_else(|| anyhow::anyhow!("Initialized client has no saved config"))?;
+            let client_builder = self
+                .create_client_builder(db.clone(), &saved_config)
+                .await?;
+            let client = client_builder.open(secret).await?;
+            let recovering = client.has_pending_recoveries();
+            return Ok((Arc::new(client), recovering));
+        }
 
-        let client_res = if Client::is_initialized(&db).await {
-            client_builder.open(secret).await
+        let client_config =
+            fedimint_api_client::download_from_invite_code(&config.invite_code).await?;
+        let client_builder = self
+            .create_client_builder(db.clone(), &client_config)
+            .await?;
+        let backup = client_builder
+            .download_backup_from_federation(&secret, &client_config, None)
+            .await?;
+
+        if recover || backup.is_some() {
+            info!(
+                "Recovering client for federation {federation_id} ({})",
+                if backup.is_some() {
+                    "from backup"
+                } else {
+                    "without backup"
+                }
+            );
+            let client = client_builder
+                .recover(secret, client_config, None, backup)
+                .await?;
+            Ok((Arc::new(client), true))
         } else {
-            let client_config =
-                fedimint_api_client::download_from_invite_code(&config.invite_code).await?;
-            client_builder
-                .join(secret, client_config.to_owned(), None)
-                .await
-        }?;
-
-        Ok(Arc::new(client_res))
+            let client = client_builder.join(secret, client_config, None).await?;
+            Ok((Arc::new(client), false))
+        }
     }
 
     /// Save the federation config to the database
@@ -95,28 +165,43 @@ impl LocalClientBuilder {
         federation_wallet_root_secret.child_key(ChildId(0))
     }
 
-    /// Verifies that the saved `ClientConfig` contains the expected
-    /// federation's config.
-    async fn verify_client_config(db: &Database, federation_id: FederationId) -> Result<()> {
+    /// Verifies that the saved `ClientConfig`, if any, contains the expected
+    /// federation's config and returns it.
+    async fn verify_client_config(
+        db: &Database,
+        federation_id: FederationId,
+    ) -> Result<Option<ClientConfig>> {
         let mut dbtx = db.begin_transaction_nc().await;
-        if let Some(config) = dbtx.get_value(&ClientConfigKey).await {
+        let config = dbtx.get_value(&ClientConfigKey).await;
+        if let Some(config) = &config {
             if config.calculate_federation_id() != federation_id {
                 anyhow::bail!("Federation Id did not match saved federation ID")
             }
         }
-        Ok(())
+        Ok(config)
+    }
+
+    /// Finds the instance id of the federation's mint module, which isn't
+    /// necessarily `1`.
+    fn primary_module_id(config: &ClientConfig) -> Result<ModuleInstanceId> {
+        config
+            .modules
+            .iter()
+            .find(|(_, module)| module.kind.as_str() == PRIMARY_MODULE_KIND)
+            .map(|(id, _)| *id)
+            .ok_or_else(|| anyhow::anyhow!("Federation has no {PRIMARY_MODULE_KIND} module"))
     }
 
     /// Constructs the client builder with the modules, database, and connector
     /// used to create clients for connected federations.
-    async fn create_client_builder(&self, db: Database) -> Result<ClientBuilder> {
-        let mut registry = ClientModuleInitRegistry::new();
-        registry.attach(WalletClientInit::default());
-        registry.attach(MintClientInit);
-        registry.attach(LightningClientInit::default());
+    async fn create_client_builder(
+        &self,
+        db: Database,
+        config: &ClientConfig,
+    ) -> Result<ClientBuilder> {
         let mut client_builder = Client::builder(db).await?;
-        client_builder.with_module_inits(registry);
-        client_builder.with_primary_module(1);
+        client_builder.with_module_inits(self.module_inits.clone());
+        client_builder.with_primary_module(Self::primary_module_id(config)?);
         Ok(client_builder)
     }
 }
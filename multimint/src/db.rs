@@ -1,13 +1,17 @@
 use fedimint_core::config::FederationId;
+use fedimint_core::core::OperationId;
 use fedimint_core::encoding::{Decodable, Encodable};
 use fedimint_core::invite_code::InviteCode;
-use fedimint_core::{impl_db_lookup, impl_db_record};
+use fedimint_core::{impl_db_lookup, impl_db_record, Amount};
 use serde::{Deserialize, Serialize};
 
 #[repr(u8)]
 #[derive(Clone, Debug)]
 pub enum DbKeyPrefix {
     FederationConfig = 0x04,
+    DefaultFederation = 0x05,
+    HealthCheck = 0x06,
+    EncryptedSecret = 0x07,
 }
 
 impl std::fmt::Display for DbKeyPrefix {
@@ -16,6 +20,28 @@ impl std::fmt::Display for DbKeyPrefix {
     }
 }
 
+/// Prefix of the database the accounts ledger is kept in. Clients store their
+/// data under their 32 byte federation id, so a single byte prefix at the top
+/// level would also match the keys of every federation whose id starts with
+/// it.
+pub const ACCOUNTS_DB_PREFIX: &[u8] = b"\xffmultimint-accounts";
+
+/// Prefixes of the records in the accounts database, see `ACCOUNTS_DB_PREFIX`
+#[repr(u8)]
+#[derive(Clone, Debug)]
+pub enum AccountsDbKeyPrefix {
+    Account = 0x01,
+    AccountEntry = 0x02,
+    AccountOperation = 0x03,
+    AccountPayment = 0x04,
+}
+
+impl std::fmt::Display for AccountsDbKeyPrefix {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{self:?}")
+    }
+}
+
 #[derive(Debug, Clone, Encodable, Decodable, Eq, PartialEq, Hash, Ord, PartialOrd)]
 pub struct FederationIdKey {
     pub id: FederationId,
@@ -36,3 +62,161 @@ impl_db_record!(
 );
 
 impl_db_lookup!(key = FederationIdKey, query_prefix = FederationIdKeyPrefix);
+
+/// The federation used when a request doesn't specify one
+#[derive(Debug, Clone, Encodable, Decodable, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct DefaultFederationKey;
+
+impl_db_record!(
+    key = DefaultFederationKey,
+    value = FederationId,
+    db_prefix = DbKeyPrefix::DefaultFederation,
+);
+
+/// Written by health checks to verify the database accepts writes
+#[derive(Debug, Clone, Encodable, Decodable, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct HealthCheckKey;
+
+impl_db_record!(
+    key = HealthCheckKey,
+    value = u64,
+    db_prefix = DbKeyPrefix::HealthCheck,
+);
+
+/// The mnemonic's entropy encrypted with a passphrase, see `secret`
+#[derive(Debug, Clone, Encodable, Decodable, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct EncryptedSecretKey;
+
+#[derive(Debug, Clone, Encodable, Decodable)]
+pub struct EncryptedSecret {
+    /// Argon2id salt
+    pub salt: [u8; 16],
+    /// ChaCha20-Poly1305 nonce
+    pub nonce: [u8; 12],
+    pub ciphertext: Vec<u8>,
+}
+
+impl_db_record!(
+    key = EncryptedSecretKey,
+    value = EncryptedSecret,
+    db_prefix = DbKeyPrefix::EncryptedSecret,
+);
+
+/// An internal account, see `accounts`
+#[derive(Debug, Clone, Encodable, Decodable, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct AccountKey {
+    pub name: String,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct AccountKeyPrefix;
+
+#[derive(Debug, Clone, Encodable, Decodable, Serialize)]
+pub struct Account {
+    pub balance: Amount,
+    /// Unix timestamp
+    pub created_at: u64,
+    /// Index of the account's next history entry
+    pub next_entry: u64,
+}
+
+impl_db_record!(
+    key = AccountKey,
+    value = Account,
+    db_prefix = AccountsDbKeyPrefix::Account,
+);
+
+impl_db_lookup!(key = AccountKey, query_prefix = AccountKeyPrefix);
+
+/// An entry of an account's history
+#[derive(Debug, Clone, Encodable, Decodable, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct AccountEntryKey {
+    pub account: String,
+    pub index: u64,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct AccountEntryKeyPrefix {
+    pub account: String,
+}
+
+#[derive(Debug, Clone, Encodable, Decodable, Serialize)]
+pub struct AccountEntry {
+    /// Unix timestamp
+    pub timestamp: u64,
+    pub kind: AccountEntryKind,
+    pub amount: Amount,
+    /// Balance after the entry
+    pub balance: Amount,
+    /// The federation operation behind deposits, payments and refunds
+    pub federation_id: Option<FederationId>,
+    pub operation_id: Option<OperationId>,
+    /// The other account of transfers
+    pub counterparty: Option<String>,
+    pub description: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Encodable, Decodable, Serialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AccountEntryKind {
+    Deposit,
+    Payment,
+    Refund,
+    TransferIn,
+    TransferOut,
+}
+
+impl_db_record!(
+    key = AccountEntryKey,
+    value = AccountEntry,
+    db_prefix = AccountsDbKeyPrefix::AccountEntry,
+);
+
+impl_db_lookup!(key = AccountEntryKey, query_prefix = AccountEntryKeyPrefix);
+
+/// Invoices to credit to an account once they are paid
+#[derive(Debug, Clone, Encodable, Decodable, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct AccountOperationKey {
+    pub operation_id: OperationId,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct AccountOperationKeyPrefix;
+
+#[derive(Debug, Clone, Encodable, Decodable, Serialize)]
+pub struct AccountOperation {
+    pub account: String,
+    pub federation_id: FederationId,
+    pub amount: Amount,
+}
+
+impl_db_record!(
+    key = AccountOperationKey,
+    value = AccountOperation,
+    db_prefix = AccountsDbKeyPrefix::AccountOperation,
+);
+
+impl_db_lookup!(
+    key = AccountOperationKey,
+    query_prefix = AccountOperationKeyPrefix
+);
+
+/// Payments debited from an account whose outcome isn't known yet
+#[derive(Debug, Clone, Encodable, Decodable, Eq, PartialEq, Hash, Ord, PartialOrd)]
+pub struct AccountPaymentKey {
+    pub operation_id: OperationId,
+}
+
+#[derive(Debug, Encodable, Decodable)]
+pub struct AccountPaymentKeyPrefix;
+
+impl_db_record!(
+    key = AccountPaymentKey,
+    value = AccountOperation,
+    db_prefix = AccountsDbKeyPrefix::AccountPayment,
+);
+
+impl_db_lookup!(
+    key = AccountPaymentKey,
+    query_prefix = AccountPaymentKeyPrefix
+);
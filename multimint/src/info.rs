@@ -0,0 +1,279 @@
+//! Wallet info of a single client, shared by `MultiMint::info` and
+//! `MultiMint::info_for`.
+
+use std::time::{Duration, SystemTime};
+
+use anyhow::Result;
+use fedimint_client::oplog::OperationLogEntry;
+use fedimint_client::ClientHandleArc;
+use fedimint_core::core::OperationId;
+use fedimint_core::{Amount, TieredCounts};
+use fedimint_ln_client::{
+    InternalPayState, LightningClientModule, LightningOperationMeta, LightningOperationMetaVariant,
+    LnPayState, LnReceiveState,
+};
+use fedimint_mint_client::{
+    MintClientModule, MintOperationMeta, MintOperationMetaVariant, SpendOOBState,
+};
+use fedimint_wallet_client::{
+    DepositStateV2, WalletClientModule, WalletOperationMeta, WalletOperationMetaVariant,
+    WithdrawState,
+};
+use serde_json::Value;
+
+use crate::operations::{latest_update, PAGE_SIZE};
+use crate::types::{InfoResponse, PendingBalances};
+
+/// Operations older than this aren't considered pending anymore
+const PENDING_WINDOW: Duration = Duration::from_secs(24 * 60 * 60);
+
+/// Most recent operations checked for pending amounts, each one's state is
+/// read concurrently
+const MAX_PENDING_OPERATIONS: usize = 100;
+
+/// Get the note counts per denomination of a client, read from the client's
+/// own database under its mint module's instance id.
+pub async fn note_summary(client: &ClientHandleArc) -> TieredCounts {
+    let mint_client = client.get_first_module::<MintClientModule>();
+    mint_client
+        .get_wallet_summary(
+            &mut client
+                .db()
+                .begin_transaction_nc()
+                .await
+                .to_ref_with_prefix_module_id(mint_client.id),
+        )
+        .await
+}
+
+/// Get the info of a client, including the balances of its pending
+/// operations.
+pub async fn client_info(client: &ClientHandleArc) -> Result<InfoResponse> {
+    let wallet_client = client.get_first_module::<WalletClientModule>();
+    let summary = note_summary(client).await;
+    let meta = client.config().await.global.meta.clone();
+    let pending = pending_balances(client).await?;
+
+    let spendable = summary.total_amount();
+    let total = spendable
+        + pending.incoming_invoices_msat
+        + pending.unconfirmed_deposits_msat
+        + pending.in_flight_spends_msat;
+
+    Ok(InfoResponse {
+        federation_id: client.federation_id(),
+        federation_name: meta.get("federation_name").cloned(),
+        network: wallet_client.get_network().to_string(),
+        meta,
+        total_amount_msat: spendable,
+        total_num_notes: summary.count_items(),
+        denominations_msat: summary,
+        spendable_msat: spendable,
+        pending,
+        total_msat: total,
+    })
+}
+
+/// Sums up the recent operations that are still in progress, according to
+/// the latest state of each one's state machine.
+///
+/// Only the last `MAX_PENDING_OPERATIONS` operations created in the last
+/// `PENDING_WINDOW` are considered, so older invoices, deposit addresses and
+/// spends that were never awaited don't count forever.
+async fn pending_balances(client: &ClientHandleArc) -> Result<PendingBalances> {
+    let window_start = SystemTime::now()
+        .checked_sub(PENDING_WINDOW)
+        .unwrap_or(SystemTime::UNIX_EPOCH);
+
+    let mut candidates = Vec::new();
+    let mut cursor = None;
+    'pages: loop {
+        let page = client
+            .operation_log()
+            .list_operations(PAGE_SIZE, cursor)
+            .await;
+        let exhausted = page.len() < PAGE_SIZE;
+
+        for (key, entry) in page {
+            cursor = Some(key);
+            if key.creation_time < window_start || candidates.len() >= MAX_PENDING_OPERATIONS {
+                break 'pages;
+            }
+            if entry.outcome::<Value>().is_none() {
+                candidates.push((key.operation_id, entry));
+            }
+        }
+
+        if exhausted {
+            break;
+        }
+    }
+
+    let amounts = futures_util::future::join_all(
+        candidates
+            .iter()
+            .map(|(operation_id, entry)| pending_amount(client, *operation_id, entry)),
+    )
+    .await;
+
+    let mut pending = PendingBalances::default();
+    for amount in amounts {
+        match amount? {
+            Some(PendingAmount::IncomingInvoice(amount)) => {
+                pending.incoming_invoices_msat += amount;
+            }
+            Some(PendingAmount::UnconfirmedDeposit(amount)) => {
+                pending.unconfirmed_deposits_msat += amount;
+            }
+            Some(PendingAmount::InFlightSpend(amount)) => pending.in_flight_spends_msat += amount,
+            Some(PendingAmount::OutgoingPayment(amount)) => {
+                pending.outgoing_payments_msat += amount;
+            }
+            None => {}
+        }
+    }
+
+    Ok(pending)
+}
+
+enum PendingAmount {
+    IncomingInvoice(Amount),
+    UnconfirmedDeposit(Amount),
+    InFlightSpend(Amount),
+    OutgoingPayment(Amount),
+}
+
+/// Amount of an operation that's still in progress, `None` once it finished
+/// or if it isn't expected to move funds.
+async fn pending_amount(
+    client: &ClientHandleArc,
+    operation_id: OperationId,
+    entry: &OperationLogEntry,
+) -> Result<Option<PendingAmount>> {
+    let pending = match entry.operation_module_kind() {
+        "ln" => {
+            let ln = client.get_first_module::<LightningClientModule>();
+            match entry.meta::<LightningOperationMeta>().variant {
+                LightningOperationMetaVariant::Receive { invoice, .. } => {
+                    let state =
+                        latest_update(ln.subscribe_ln_receive(operation_id).await?.into_stream())
+                            .await;
+                    let waiting = match state {
+                        Some(
+                            LnReceiveState::Created | LnReceiveState::WaitingForPayment { .. },
+                        ) => !invoice.is_expired(),
+                        Some(LnReceiveState::Funded | LnReceiveState::AwaitingFunds) => true,
+                        _ => false,
+                    };
+                    waiting.then(|| {
+                        PendingAmount::IncomingInvoice(Amount::from_msats(
+                            invoice.amount_milli_satoshis().unwrap_or(0),
+                        ))
+                    })
+                }
+                LightningOperationMetaVariant::Pay(pay) => {
+                    let in_progress = if pay.is_internal_payment {
+                        matches!(
+                            latest_update(
+                                ln.subscribe_internal_pay(operation_id).await?.into_stream()
+                            )
+                            .await,
+                            Some(InternalPayState::Funding)
+                        )
+                    } else {
+                        matches!(
+                            latest_update(ln.subscribe_ln_pay(operation_id).await?.into_stream())
+                                .await,
+                            Some(
+                                LnPayState::Created
+                                    | LnPayState::Funded { .. }
+                                    | LnPayState::WaitingForRefund { .. }
+                                    | LnPayState::AwaitingChange
+                            )
+                        )
+                    };
+                    in_progress.then(|| {
+                        PendingAmount::OutgoingPayment(
+                            Amount::from_msats(pay.invoice.amount_milli_satoshis().unwrap_or(0))
+                                + pay.fee,
+                        )
+                    })
+                }
+                LightningOperationMetaVariant::Claim { .. } => None,
+            }
+        }
+        "mint" => {
+            let meta = entry.meta::<MintOperationMeta>();
+            match meta.variant {
+                MintOperationMetaVariant::SpendOOB { .. } => {
+                    let mint = client.get_first_module::<MintClientModule>();
+                    let state = latest_update(
+                        mint.subscribe_spend_notes(operation_id)
+                            .await?
+                            .into_stream(),
+                    )
+                    .await;
+                    matches!(
+                        state,
+                        Some(SpendOOBState::Created | SpendOOBState::UserCanceledProcessing)
+                    )
+                    .then_some(PendingAmount::InFlightSpend(meta.amount))
+                }
+                MintOperationMetaVariant::Reissuance { .. } => None,
+            }
+        }
+        "wallet" => {
+            let wallet = client.get_first_module::<WalletClientModule>();
+            match entry.meta::<WalletOperationMeta>().variant {
+                WalletOperationMetaVariant::Deposit { .. } => {
+                    unconfirmed_deposit(&wallet, operation_id)
+                        .await?
+                        .map(PendingAmount::UnconfirmedDeposit)
+                }
+                WalletOperationMetaVariant::Withdraw { amount, fee, .. } => {
+                    let state = latest_update(
+                        wallet
+                            .subscribe_withdraw_updates(operation_id)
+                            .await?
+                            .into_stream(),
+                    )
+                    .await;
+                    matches!(state, Some(WithdrawState::Created)).then(|| {
+                        PendingAmount::OutgoingPayment(
+                            Amount::from_sats(amount.to_sat())
+                                + Amount::from_sats(fee.amount().to_sat()),
+                        )
+                    })
+                }
+                _ => None,
+            }
+        }
+        _ => None,
+    };
+
+    Ok(pending)
+}
+
+/// Most deposit operations are addresses that never receive a transaction, so
+/// only the ones that are waiting for confirmations or to be claimed are
+/// counted.
+async fn unconfirmed_deposit(
+    wallet_client: &WalletClientModule,
+    operation_id: OperationId,
+) -> Result<Option<Amount>> {
+    let latest = latest_update(
+        wallet_client
+            .subscribe_deposit(operation_id)
+            .await?
+            .into_stream(),
+    )
+    .await;
+
+    Ok(match latest {
+        Some(
+            DepositStateV2::WaitingForConfirmation { btc_deposited, .. }
+            | DepositStateV2::Confirmed { btc_deposited, .. },
+        ) => Some(Amount::from_sats(btc_deposited.to_sat())),
+        _ => None,
+    })
+}
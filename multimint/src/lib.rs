@@ -7,6 +7,11 @@
 //! by `FederationId`, and provides methods for managing and interacting with
 //! the clients.
 //!
+//! The map's lock is only held to look up or clone client handles, never while
+//! talking to a federation, so a slow or unreachable federation doesn't hold up
+//! requests for the others. Methods covering all the clients query them
+//! concurrently.
+//!
 //! Multimint uses 1 top level directory for all its data, and creates
 //! subdirectories for each client. Each client's directory behaves like a
 //! standalone Fedimint client.
@@ -22,38 +27,41 @@
 //! When you create a new `MultiMint` instance you pass it a path to the top
 //! level directory for all its data. If the directory does not exist it will be
 //! created. If the directory already has data from a previous run, it will be
-//! loaded.
+//! loaded. Alternatively `MultiMintBuilder::build_with_database` takes any
+//! `Database`, and `MultiMint::new_in_memory` keeps everything in memory.
 //!
 //! Example:
 //!
-//! ```rust
-//! use multimint::MultiMint;
+//! ```no_run
 //! use std::path::PathBuf;
+//! use std::str::FromStr;
+//!
+//! use multimint::fedimint_core::invite_code::InviteCode;
+//! use multimint::MultiMint;
 //!
 //! #[tokio::main]
 //! async fn main() -> Result<(), Box<dyn std::error::Error>> {
-//!    let work_dir = PathBuf::from("/path/to/fm_data_dir");
+//!     let work_dir = PathBuf::from("/path/to/fm_data_dir");
+//!
+//!     // `new` handles creating a new multimint with no clients or will load the existing databases in the work_dir into ClientHandleArcs
+//!     let mut multimint = MultiMint::new(work_dir).await?;
+//!
+//!     // List the ids of the federations the multimint has clients for
+//!     // E.g. if the work_dir has 2 clients, the ids will be [FederationId, FederationId]
+//!     // If there are no clients, the ids will be an empty vector
+//!     let federation_ids = multimint.ids().await;
+//!     println!("Federation IDs: {:?}", federation_ids);
 //!
-//!    // `new` handles creating a new multimint with no clients or will load the existing databases in the work_dir into ClientHandleArcs
-//!    let multimint = MultiMint::new(work_dir).await?;
+//!     // Create a new client by connecting to a federation with an invite code
+//!     let invite_code = InviteCode::from_str("fed1_invite_code")?;
+//!     // The client's secret is derived from the multimint's mnemonic
+//!     let federation_id = multimint.register_new(invite_code).await?;
 //!
-//!    // List the ids of the federations the multimint has clients for
-//!    // E.g. if the work_dir has 2 clients, the ids will be [FederationId, FederationId]
-//!    // If there are no clients, the ids will be an empty vector
-//!    let federation_ids = multimint.ids().await;
-//!    println!("Federation IDs: {:?}", federation_ids);
+//!     // Get a client by its federation id
+//!     let client = multimint.get(&federation_id).await.ok_or("Client not found")?;
+//!     println!("Client: {:?}", client.federation_id());
 //!
-//!    // Create a new client by connecting to a federation with an invite code
-//!    let invite_code = "fed1_invite_code";
-//!    // The client's keypair is created based off a 64 byte random secret that is either generated or provided by the user
-//!    let secret = env::var("FM_SECRET").ok_or(None);
-//!     multimint.register_new(invite_code, secret).await?;
-//!    
-//!    // Get a client by its federation id
-//!    let client = multimint.get(&federation_ids[0]).await?;
-//!    println!("Client: {:?}", client);
-//!    
-//!    Ok(())
+//!     Ok(())
 //! }
 //! ```
 //!
@@ -62,38 +70,130 @@
 //! balances.
 
 use std::collections::BTreeMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
-use std::sync::Arc;
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::Duration;
 
 use anyhow::Result;
-use bip39::Mnemonic;
-use fedimint_bip39::Bip39RootSecretStrategy;
-use fedimint_client::secret::RootSecretStrategy;
-use fedimint_client::{Client, ClientHandleArc};
+use fedimint_client::module::init::{ClientModuleInit, ClientModuleInitRegistry};
+use fedimint_client::{ClientHandle, ClientHandleArc};
 use fedimint_core::config::{FederationId, FederationIdPrefix, JsonClientConfig};
-use fedimint_core::db::Database;
+use fedimint_core::db::mem_impl::MemDatabase;
+use fedimint_core::db::{Database, IDatabaseTransactionOpsCoreTyped};
 use fedimint_core::invite_code::InviteCode;
 use fedimint_core::Amount;
 use fedimint_ln_client::LightningClientModule;
-use fedimint_mint_client::MintClientModule;
-use fedimint_wallet_client::WalletClientModule;
-use rand::thread_rng;
+use futures_util::future::{join_all, BoxFuture};
+use futures_util::StreamExt;
 use tokio::sync::Mutex;
-use tracing::{info, warn};
-use types::InfoResponse;
+use tracing::{debug, info, warn};
+use types::{GatewayCacheStatus, InfoResponse, LoadStatus, ModuleRecoveryProgress, RecoveryStatus};
 // Reexport all the fedimint crates for ease of use
+#[cfg(feature = "lnv2")]
+pub use fedimint_lnv2_client;
+#[cfg(feature = "meta")]
+pub use fedimint_meta_client;
 pub use {
-    fedimint_client, fedimint_core, fedimint_ln_client, fedimint_ln_common, fedimint_mint_client,
-    fedimint_wallet_client,
+    fedimint_api_client, fedimint_client, fedimint_core, fedimint_ln_client, fedimint_ln_common,
+    fedimint_mint_client, fedimint_wallet_client,
 };
 
+pub mod accounts;
 pub mod client;
 pub mod db;
+pub mod info;
+pub mod operations;
+pub mod secret;
 pub mod types;
 
-use crate::client::LocalClientBuilder;
-use crate::db::FederationConfig;
+use crate::client::{default_module_inits, LocalClientBuilder};
+use crate::db::{DefaultFederationKey, FederationConfig, FederationIdKey, HealthCheckKey};
+
+/// Builds a `MultiMint` whose clients use a custom set of client modules.
+#[derive(Debug, Clone)]
+pub struct MultiMintBuilder {
+    module_inits: ClientModuleInitRegistry,
+    passphrase: Option<String>,
+    mnemonic_from_env: bool,
+}
+
+impl Default for MultiMintBuilder {
+    fn default() -> Self {
+        Self {
+            module_inits: default_module_inits(),
+            passphrase: None,
+            mnemonic_from_env: true,
+        }
+    }
+}
+
+impl MultiMintBuilder {
+    /// Attach an additional client module, e.g. for a module the federation
+    /// runs that isn't attached by default.
+    pub fn with_module<T: ClientModuleInit>(mut self, module_init: T) -> Self {
+        self.module_inits.attach(module_init);
+        self
+    }
+
+    /// Replace the client modules, which should include at least the mint
+    /// module. Starts from `default_module_inits()` otherwise.
+    pub fn with_module_inits(mut self, module_inits: ClientModuleInitRegistry) -> Self {
+        self.module_inits = module_inits;
+        self
+    }
+
+    /// Unlock the stored mnemonic with `passphrase`, or encrypt it with it if
+    /// it's stored in plaintext or newly generated. See `secret`.
+    pub fn with_passphrase(mut self, passphrase: String) -> Self {
+        self.passphrase = Some(passphrase);
+        self
+    }
+
+    /// Always generate a random mnemonic for a new database instead of taking
+    /// it from `MULTIMINT_MNEMONIC_ENV`, for a multimint that must not share
+    /// the seed of another one in the same process.
+    pub fn without_env_mnemonic(mut self) -> Self {
+        self.mnemonic_from_env = false;
+        self
+    }
+
+    /// Open the multimint in `work_dir`, see `MultiMint::new`.
+    pub async fn build(self, work_dir: PathBuf) -> Result<MultiMint> {
+        self.build_with_database(open_database(&work_dir)?).await
+    }
+
+    /// Open the multimint in `db`, which can be any database backend. The
+    /// clients' data is stored in it as well, under their federation id.
+    pub async fn build_with_database(self, db: Database) -> Result<MultiMint> {
+        MultiMint::open(
+            db,
+            self.module_inits,
+            self.passphrase.as_deref(),
+            self.mnemonic_from_env,
+        )
+        .await
+    }
+}
+
+/// Open the RocksDB database of the multimint in `work_dir`, e.g. to change
+/// the passphrase of its mnemonic with `secret::change_passphrase`.
+pub fn open_database(work_dir: &Path) -> Result<Database> {
+    Ok(Database::new(
+        fedimint_rocksdb::RocksDb::open(work_dir.join("multimint.db"))?,
+        Default::default(),
+    ))
+}
+
+type ClientMap = BTreeMap<FederationId, ClientHandleArc>;
+
+/// Delay before retrying to open a client that failed to load, doubled after
+/// every failed attempt up to `MAX_LOAD_RETRY_DELAY`
+const LOAD_RETRY_DELAY: Duration = Duration::from_secs(1);
+const MAX_LOAD_RETRY_DELAY: Duration = Duration::from_secs(5 * 60);
+
+/// How long `reopen` waits for the other holders of a client to let go of it
+const REOPEN_TIMEOUT: Duration = Duration::from_secs(60);
 
 /// `MultiMint` is a struct for managing Fedimint Clients across multiple
 /// federations.
@@ -101,7 +201,11 @@ use crate::db::FederationConfig;
 pub struct MultiMint {
     db: Database,
     pub client_builder: LocalClientBuilder,
-    pub clients: Arc<Mutex<BTreeMap<FederationId, ClientHandleArc>>>,
+    /// Never held across an await, use `all()` to work on the clients
+    clients: Arc<RwLock<ClientMap>>,
+    recoveries: Arc<Mutex<BTreeMap<FederationId, RecoveryStatus>>>,
+    loading: Arc<Mutex<BTreeMap<FederationId, LoadStatus>>>,
+    gateway_caches: Arc<Mutex<BTreeMap<FederationId, GatewayCacheStatus>>>,
 }
 
 impl MultiMint {
@@ -113,78 +217,200 @@ impl MultiMint {
     ///
     /// # Example
     ///
-    /// ```rust
-    /// use multimint::MultiMint;
+    /// ```no_run
     /// use std::path::PathBuf;
+    /// use std::str::FromStr;
+    ///
+    /// use multimint::fedimint_core::invite_code::InviteCode;
+    /// use multimint::MultiMint;
     ///
     /// #[tokio::main]
     /// async fn main() -> Result<(), Box<dyn std::error::Error>> {
-    ///   let work_dir = PathBuf::from("/path/to/fm_data_dir");
+    ///     let work_dir = PathBuf::from("/path/to/fm_data_dir");
+    ///
+    ///     // `new` handles creating a new multimint with no clients or will load the existing databases in the work_dir into ClientHandleArcs
+    ///     let mut multimint = MultiMint::new(work_dir).await?;
     ///
-    ///   // `new` handles creating a new multimint with no clients or will load the existing databases in the work_dir into ClientHandleArcs
-    ///  let multimint = MultiMint::new(work_dir).await?;
+    ///     // List the ids of the federations the multimint has clients for
+    ///     // E.g. if the work_dir has 2 clients, the ids will be [FederationId, FederationId]
+    ///     // If there are no clients, the ids will be an empty vector
+    ///     let federation_ids = multimint.ids().await;
+    ///     println!("Federation IDs: {:?}", federation_ids);
     ///
-    ///   // List the ids of the federations the multimint has clients for
-    ///  // E.g. if the work_dir has 2 clients, the ids will be [FederationId, FederationId]
-    /// // If there are no clients, the ids will be an empty vector
-    /// let federation_ids = multimint.ids().await;
-    /// println!("Federation IDs: {:?}", federation_ids);
+    ///     // Create a new client by connecting to a federation with an invite code
+    ///     let invite_code = InviteCode::from_str("fed1_invite_code")?;
+    ///     // The client's secret is derived from the multimint's mnemonic
+    ///     let federation_id = multimint.register_new(invite_code).await?;
     ///
-    ///   // Create a new client by connecting to a federation with an invite code
-    ///   let invite_code = "fed1_invite_code";
-    ///  // The client's keypair is created based off a 64 byte random secret that is either generated or provided by the user
-    ///  let secret = env::var("FM_SECRET").ok_or(None);
-    ///     multimint.register_new(invite_code, secret).await?;
-    ///    
-    ///   // Get a client by its federation id
-    ///   let client = multimint.get(&federation_ids[0]).await?;
-    ///   println!("Client: {:?}", client);
-    ///    
-    ///   Ok(())
+    ///     // Get a client by its federation id
+    ///     let client = multimint.get(&federation_id).await.ok_or("Client not found")?;
+    ///     println!("Client: {:?}", client.federation_id());
+    ///
+    ///     Ok(())
     /// }
     /// ```
     pub async fn new(work_dir: PathBuf) -> Result<Self> {
-        let db = Database::new(
-            fedimint_rocksdb::RocksDb::open(work_dir.join("multimint.db"))?,
-            Default::default(),
-        );
-        let mnemonic = load_or_generate_mnemonic(&db).await?;
+        Self::builder().build(work_dir).await
+    }
+
+    /// Create a `MultiMintBuilder` to customize the client modules attached to
+    /// the clients.
+    ///
+    /// # Example
+    ///
+    /// ```rust,ignore
+    /// let multimint = MultiMint::builder()
+    ///     .with_module(fedimint_meta_client::MetaClientInit)
+    ///     .build(work_dir)
+    ///     .await?;
+    /// ```
+    pub fn builder() -> MultiMintBuilder {
+        MultiMintBuilder::default()
+    }
 
-        let client_builder = LocalClientBuilder::new(mnemonic);
+    /// Create a `MultiMint` backed by an in-memory database, e.g. for tests.
+    /// Nothing is persisted, so the mnemonic and clients are lost when it is
+    /// dropped.
+    pub async fn new_in_memory() -> Result<Self> {
+        Self::builder()
+            .build_with_database(Database::new(MemDatabase::new(), Default::default()))
+            .await
+    }
 
-        let clients = Arc::new(Mutex::new(BTreeMap::new()));
+    async fn open(
+        db: Database,
+        module_inits: ClientModuleInitRegistry,
+        passphrase: Option<&str>,
+        mnemonic_from_env: bool,
+    ) -> Result<Self> {
+        let mnemonic =
+            secret::load_or_generate_mnemonic(&db, passphrase, mnemonic_from_env).await?;
 
-        Self::load_clients(&mut clients.clone(), &db, &client_builder).await?;
+        let client_builder = LocalClientBuilder::with_module_inits(mnemonic, module_inits);
 
-        Ok(Self {
+        let multimint = Self {
             db,
             client_builder,
-            clients,
-        })
+            clients: Default::default(),
+            recoveries: Default::default(),
+            loading: Default::default(),
+            gateway_caches: Default::default(),
+        };
+
+        multimint.load_clients().await;
+
+        Ok(multimint)
     }
 
     /// Load the clients from from the top level database in the work directory
-    async fn load_clients(
-        clients: &mut Arc<Mutex<BTreeMap<FederationId, ClientHandleArc>>>,
-        db: &Database,
-        client_builder: &LocalClientBuilder,
-    ) -> Result<()> {
-        let mut clients = clients.lock().await;
-
-        let dbtx = db.begin_transaction().await;
-        let configs = client_builder.load_configs(dbtx.into_nc()).await;
+    /// in the background, see `load_client`.
+    async fn load_clients(&self) {
+        let dbtx = self.db.begin_transaction().await;
+        let configs = self.client_builder.load_configs(dbtx.into_nc()).await;
 
         for config in configs {
-            let federation_id = config.invite_code.federation_id();
+            self.load_client(config).await;
+        }
+    }
 
-            if let Ok(client) = client_builder.build(&db, config.clone()).await {
-                clients.insert(federation_id, client);
-            } else {
-                warn!("Failed to load client for federation: {federation_id}");
+    /// Opens the client for `config` in a background task, retrying with an
+    /// exponential backoff until it succeeds. Until then the client is listed
+    /// by `loading` instead of `all`.
+    async fn load_client(&self, config: FederationConfig) {
+        let federation_id = config.invite_code.federation_id();
+        self.loading
+            .lock()
+            .await
+            .insert(federation_id, LoadStatus::default());
+
+        let multimint = self.clone();
+        tokio::spawn(async move {
+            let mut retry_delay = LOAD_RETRY_DELAY;
+            loop {
+                let res = multimint
+                    .client_builder
+                    .build(&multimint.db, config.clone())
+                    .await;
+
+                let mut loading = multimint.loading.lock().await;
+                // Shut down while loading
+                let Some(status) = loading.get_mut(&federation_id) else {
+                    return;
+                };
+
+                match res {
+                    Ok(client) => {
+                        multimint
+                            .write_clients()
+                            .insert(federation_id, client.clone());
+                        loading.remove(&federation_id);
+                        drop(loading);
+                        info!("Loaded client for federation: {federation_id}");
+
+                        // Recoveries interrupted by a restart continue when the
+                        // client is opened
+                        if client.has_pending_recoveries() {
+                            multimint.track_recovery(federation_id, client).await;
+                        } else {
+                            // Failures are logged and recorded
+                            let _ = multimint.update_gateway_cache(&client).await;
+                        }
+                        return;
+                    }
+                    Err(e) => {
+                        warn!(
+                            "Failed to load client for federation {federation_id}, retrying in {retry_delay:?}: {e:?}"
+                        );
+                        status.attempts += 1;
+                        status.error = Some(e.to_string());
+                        status.next_attempt = Some(
+                            fedimint_core::time::duration_since_epoch().as_secs()
+                                + retry_delay.as_secs(),
+                        );
+                    }
+                }
+                drop(loading);
+
+                tokio::time::sleep(retry_delay).await;
+                retry_delay = (retry_delay * 2).min(MAX_LOAD_RETRY_DELAY);
             }
+        });
+    }
+
+    /// Get the load status of the clients that are still being opened.
+    pub async fn loading(&self) -> BTreeMap<FederationId, LoadStatus> {
+        self.loading.lock().await.clone()
+    }
+
+    /// Wait until every client is opened or failed to open at least once, for
+    /// callers that need the clients right away rather than serve requests.
+    ///
+    /// Returns the clients that failed to open, with the last error, they keep
+    /// being retried in the background.
+    pub async fn wait_for_loading(&self) -> BTreeMap<FederationId, LoadStatus> {
+        loop {
+            let loading = self.loading.lock().await.clone();
+            if loading.values().all(|status| status.attempts > 0) {
+                return loading;
+            }
+            tokio::time::sleep(Duration::from_millis(100)).await;
         }
+    }
 
-        Ok(())
+    /// Check if a client is still being opened, in which case it isn't
+    /// returned by `get` yet.
+    pub async fn is_loading(&self, federation_id: &FederationId) -> bool {
+        self.loading.lock().await.contains_key(federation_id)
+    }
+
+    fn read_clients(&self) -> RwLockReadGuard<'_, ClientMap> {
+        // The map is only ever updated by single inserts and removals, so it's
+        // consistent even if a writer panicked
+        self.clients.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    fn write_clients(&self) -> RwLockWriteGuard<'_, ClientMap> {
+        self.clients.write().unwrap_or_else(PoisonError::into_inner)
     }
 
     /// Register a new client by connecting to a federation with an invite code.
@@ -195,51 +421,264 @@ impl MultiMint {
     /// don't provide a secret, a 64 byte random secret will be generated, which
     /// you can extract from the client if needed.
     pub async fn register_new(&mut self, invite_code: InviteCode) -> Result<FederationId> {
+        let (federation_id, _) = self.register_new_with_recovery(invite_code, false).await?;
+        Ok(federation_id)
+    }
+
+    /// Register a new client like `register_new`, recovering the funds of a
+    /// previous client with the same secret from the federation's history
+    /// if `recover` is set or the federation holds a backup for it.
+    ///
+    /// Returns whether the client is recovering. While it is, its modules are
+    /// unavailable and `recovery_status` reports the progress. Once the
+    /// recovery completes the client is reopened.
+    pub async fn register_new_with_recovery(
+        &mut self,
+        invite_code: InviteCode,
+        recover: bool,
+    ) -> Result<(FederationId, bool)> {
         let federation_id = invite_code.federation_id();
-        if self
-            .clients
-            .lock()
-            .await
-            .get(&invite_code.federation_id())
-            .is_some()
-        {
+        if self.has(&federation_id).await {
             warn!(
                 "Federation already registered: {:?}",
                 invite_code.federation_id()
             );
-            return Ok(federation_id);
+            return Ok((federation_id, self.is_recovering(&federation_id).await));
+        }
+        if self.is_loading(&federation_id).await {
+            warn!("Federation already registered and still loading: {federation_id}");
+            return Ok((federation_id, false));
         }
 
         let client_cfg = FederationConfig { invite_code };
 
-        let client = self
+        let (client, recovering) = self
             .client_builder
-            .build(&self.db, client_cfg.clone())
+            .join_or_recover(&self.db, client_cfg.clone(), recover)
             .await?;
 
-        self.clients.lock().await.insert(federation_id, client);
+        self.write_clients().insert(federation_id, client.clone());
 
         let dbtx = self.db.begin_transaction().await;
         self.client_builder
             .save_config(client_cfg.clone(), dbtx)
             .await?;
 
-        Ok(federation_id)
+        if recovering {
+            self.track_recovery(federation_id, client).await;
+        }
+
+        Ok((federation_id, recovering))
+    }
+
+    /// Get the recovery progress of a client, if it was recovered since the
+    /// multimint was created.
+    pub async fn recovery_status(&self, federation_id: &FederationId) -> Option<RecoveryStatus> {
+        self.recoveries.lock().await.get(federation_id).cloned()
+    }
+
+    /// Check if a client is still recovering, in which case its modules are
+    /// unavailable.
+    pub async fn is_recovering(&self, federation_id: &FederationId) -> bool {
+        self.recoveries
+            .lock()
+            .await
+            .get(federation_id)
+            .is_some_and(|status| !status.complete)
+    }
+
+    /// Records the recovery progress of `client` until all its modules are
+    /// recovered, then reopens it so the recovered modules are initialized.
+    async fn track_recovery(&self, federation_id: FederationId, client: ClientHandleArc) {
+        self.recoveries
+            .lock()
+            .await
+            .insert(federation_id, RecoveryStatus::default());
+
+        let multimint = self.clone();
+        tokio::spawn(async move {
+            let res = {
+                let mut progress = client.subscribe_to_recovery_progress();
+                let wait = client.wait_for_all_recoveries();
+                tokio::pin!(wait);
+                loop {
+                    tokio::select! {
+                        res = &mut wait => break res,
+                        Some((module_id, module_progress)) = progress.next() => {
+                            if let Some(status) =
+                                multimint.recoveries.lock().await.get_mut(&federation_id)
+                            {
+                                status.modules.insert(
+                                    module_id,
+                                    ModuleRecoveryProgress {
+                                        complete: module_progress.complete,
+                                        total: module_progress.total,
+                                    },
+                                );
+                            }
+                        }
+                    }
+                }
+            };
+            drop(client);
+
+            let res = match res {
+                Ok(()) => {
+                    info!("Recovery complete for federation: {federation_id}");
+                    multimint.reopen(federation_id).await
+                }
+                Err(e) => Err(e),
+            };
+
+            if let Some(status) = multimint.recoveries.lock().await.get_mut(&federation_id) {
+                match res {
+                    Ok(()) => status.complete = true,
+                    Err(e) => {
+                        warn!("Recovery failed for federation {federation_id}: {e:?}");
+                        status.error = Some(e.to_string());
+                    }
+                }
+            }
+        });
+    }
+
+    /// Shut down a client and open it again from its database.
+    ///
+    /// The client stays available until nothing else holds on to it, for at
+    /// most `REOPEN_TIMEOUT`. If it can't be opened again it is loaded in the
+    /// background like at startup, so the federation isn't lost.
+    ///
+    /// The future is boxed because a client that fails to reopen is loaded
+    /// again, which may track its recovery and reopen it: the compiler can't
+    /// prove the futures of that cycle `Send` otherwise.
+    fn reopen(&self, federation_id: FederationId) -> BoxFuture<'_, Result<()>> {
+        Box::pin(async move {
+            let config = self
+                .db
+                .begin_transaction_nc()
+                .await
+                .get_value(&FederationIdKey { id: federation_id })
+                .await
+                .ok_or_else(|| {
+                    anyhow::anyhow!("No config found for federation: {federation_id}")
+                })?;
+
+            let client = tokio::time::timeout(REOPEN_TIMEOUT, async {
+                loop {
+                    if let Some(client) = self.take_unshared_client(&federation_id)? {
+                        return Ok::<_, anyhow::Error>(client);
+                    }
+                    tokio::time::sleep(Duration::from_millis(100)).await;
+                }
+            })
+            .await
+            .map_err(|_| {
+                anyhow::anyhow!(
+                    "Timed out after {REOPEN_TIMEOUT:?} waiting for the client of {federation_id} to be released"
+                )
+            })??;
+            client.shutdown().await;
+
+            match self.client_builder.build(&self.db, config.clone()).await {
+                Ok(client) => {
+                    self.write_clients().insert(federation_id, client);
+                }
+                Err(e) => {
+                    warn!("Failed to reopen client for federation {federation_id}, loading it in the background: {e:?}");
+                    self.load_client(config).await;
+                }
+            }
+            Ok(())
+        })
+    }
+
+    /// Removes a client from the map if nothing else holds on to it, otherwise
+    /// leaves it in place.
+    fn take_unshared_client(&self, federation_id: &FederationId) -> Result<Option<ClientHandle>> {
+        let mut clients = self.write_clients();
+        let Some(client) = clients.remove(federation_id) else {
+            anyhow::bail!("No client found for federation: {federation_id}");
+        };
+        match Arc::try_unwrap(client) {
+            Ok(client) => Ok(Some(client)),
+            Err(shared) => {
+                clients.insert(*federation_id, shared);
+                Ok(None)
+            }
+        }
+    }
+
+    /// Set the federation used when callers don't specify one. The multimint
+    /// must already have a client for it, or be loading one.
+    pub async fn set_default_federation(&self, federation_id: FederationId) -> Result<()> {
+        if !self.has(&federation_id).await && !self.is_loading(&federation_id).await {
+            anyhow::bail!("No client found for federation: {federation_id}");
+        }
+
+        let mut dbtx = self.db.begin_transaction().await;
+        dbtx.insert_entry(&DefaultFederationKey, &federation_id)
+            .await;
+        dbtx.commit_tx_result()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to save default federation: {:?}", e))
+    }
+
+    /// Get the internal accounts kept in the multimint database.
+    pub fn accounts(&self) -> accounts::Accounts {
+        accounts::Accounts::new(&self.db)
+    }
+
+    /// Check that the multimint database accepts writes by committing the
+    /// current time under a dedicated key.
+    pub async fn check_db_writeable(&self) -> Result<()> {
+        let now = fedimint_core::time::duration_since_epoch().as_secs();
+        let mut dbtx = self.db.begin_transaction().await;
+        dbtx.insert_entry(&HealthCheckKey, &now).await;
+        dbtx.commit_tx_result()
+            .await
+            .map_err(|e| anyhow::anyhow!("Failed to write to database: {:?}", e))
+    }
+
+    /// Get the default federation, if one was set.
+    pub async fn default_federation(&self) -> Option<FederationId> {
+        self.db
+            .begin_transaction_nc()
+            .await
+            .get_value(&DefaultFederationKey)
+            .await
+    }
+
+    /// Get a client by its federation id, or the default federation's client
+    /// if no id is given.
+    pub async fn get_or_default(
+        &self,
+        federation_id: Option<FederationId>,
+    ) -> Option<ClientHandleArc> {
+        match federation_id {
+            Some(federation_id) => self.get(&federation_id).await,
+            None => self.get(&self.default_federation().await?).await,
+        }
     }
 
     /// Get all the clients in the multimint.
     pub async fn all(&self) -> Vec<ClientHandleArc> {
-        self.clients.lock().await.values().cloned().collect()
+        self.read_clients().values().cloned().collect()
+    }
+
+    /// Get a snapshot of the clients by federation id. Clients opened or
+    /// removed afterwards aren't reflected in it.
+    pub async fn clients(&self) -> BTreeMap<FederationId, ClientHandleArc> {
+        self.read_clients().clone()
     }
 
     /// Get the ids of the federations the multimint has clients for.
     pub async fn ids(&self) -> Vec<FederationId> {
-        self.clients.lock().await.keys().cloned().collect()
+        self.read_clients().keys().cloned().collect()
     }
 
     /// Get a client by its federation id.
     pub async fn get(&self, federation_id: &FederationId) -> Option<ClientHandleArc> {
-        self.clients.lock().await.get(federation_id).cloned()
+        self.read_clients().get(federation_id).cloned()
     }
 
     /// Get a client by its federation id as a string. (Useful for passing in
@@ -255,36 +694,48 @@ impl MultiMint {
         &self,
         federation_id_prefix: &FederationIdPrefix,
     ) -> Option<ClientHandleArc> {
-        let keys = self
-            .clients
-            .lock()
-            .await
-            .keys()
-            .cloned()
-            .collect::<Vec<_>>();
-        let federation_id = keys
-            .into_iter()
-            .find(|id| id.to_prefix() == *federation_id_prefix);
-
-        match federation_id {
-            Some(federation_id) => self.get(&federation_id).await,
-            None => None,
-        }
+        self.read_clients()
+            .iter()
+            .find(|(id, _)| id.to_prefix() == *federation_id_prefix)
+            .map(|(_, client)| client.clone())
     }
 
     /// Update a client by its federation id.
     pub async fn update(&self, federation_id: &FederationId, new_client: ClientHandleArc) {
-        self.clients.lock().await.insert(*federation_id, new_client);
+        self.write_clients().insert(*federation_id, new_client);
     }
 
     /// Remove a client by its federation id.
     pub async fn remove(&self, federation_id: &FederationId) {
-        self.clients.lock().await.remove(federation_id);
+        self.write_clients().remove(federation_id);
+    }
+
+    /// Shut down all the clients in the multimint, waiting for their
+    /// executors to stop.
+    ///
+    /// Clients still referenced elsewhere can't be shut down here, they will
+    /// shut down in the background once the last reference is dropped.
+    pub async fn shutdown(&self) {
+        // Stops the background loads from adding clients
+        self.loading.lock().await.clear();
+        let clients = std::mem::take(&mut *self.write_clients());
+
+        for (federation_id, client) in clients {
+            match Arc::try_unwrap(client) {
+                Ok(client) => {
+                    client.shutdown().await;
+                    info!("Shut down client for federation: {federation_id}");
+                }
+                Err(_) => {
+                    warn!("Client for federation {federation_id} is still in use, it will shut down once released");
+                }
+            }
+        }
     }
 
     /// Check if a client exists by its federation id.
     pub async fn has(&self, federation_id: &FederationId) -> bool {
-        self.clients.lock().await.contains_key(federation_id)
+        self.read_clients().contains_key(federation_id)
     }
 
     /// Check if a client exists by its federation id as a string.
@@ -299,98 +750,200 @@ impl MultiMint {
 
     /// Get the configs for all the clients in the multimint.
     pub async fn configs(&self) -> Result<BTreeMap<FederationId, JsonClientConfig>> {
-        let mut configs_map = BTreeMap::new();
-        let clients = self.clients.lock().await;
-
-        for (federation_id, client) in clients.iter() {
-            let client_config = client.get_config_json().await;
-            configs_map.insert(*federation_id, client_config);
-        }
-
-        Ok(configs_map)
+        let clients = self.all().await;
+        let configs = join_all(clients.iter().map(|client| client.get_config_json())).await;
+
+        Ok(clients
+            .iter()
+            .map(|client| client.federation_id())
+            .zip(configs)
+            .collect())
     }
 
     /// Get the balances for all the clients in the multimint.
     pub async fn ecash_balances(&self) -> Result<BTreeMap<FederationId, Amount>> {
-        let mut balances = BTreeMap::new();
-        let clients = self.clients.lock().await;
+        let clients = self.all().await;
+        let balances = join_all(clients.iter().map(|client| client.get_balance())).await;
+
+        Ok(clients
+            .iter()
+            .map(|client| client.federation_id())
+            .zip(balances)
+            .collect())
+    }
 
-        for (federation_id, client) in clients.iter() {
-            let balance = client.get_balance().await;
-            balances.insert(*federation_id, balance);
+    /// Get the info for all the clients in the multimint. A client failing to
+    /// report its info doesn't affect the others.
+    pub async fn info(&self) -> BTreeMap<FederationId, Result<InfoResponse>> {
+        let mut clients = Vec::new();
+        for client in self.all().await {
+            // Modules of recovering clients aren't available yet
+            if !self.is_recovering(&client.federation_id()).await {
+                clients.push(client);
+            }
         }
 
-        Ok(balances)
+        let infos = join_all(clients.iter().map(info::client_info)).await;
+        clients
+            .iter()
+            .map(|client| client.federation_id())
+            .zip(infos)
+            .collect()
     }
 
-    /// Get the info for all the clients in the multimint.
-    pub async fn info(&self) -> Result<BTreeMap<FederationId, InfoResponse>> {
-        let mut info_map = BTreeMap::new();
-        let clients = self.clients.lock().await;
-
-        for (federation_id, client) in clients.iter() {
-            let mint_client = client.get_first_module::<MintClientModule>();
-            let wallet_client = client.get_first_module::<WalletClientModule>();
-            let summary = mint_client
-                .get_wallet_summary(
-                    &mut self
-                        .db
-                        .begin_transaction_nc()
-                        .await
-                        .to_ref_with_prefix_module_id(1),
-                )
-                .await;
-
-            let info = InfoResponse {
-                federation_id: *federation_id,
-                network: wallet_client.get_network().to_string(),
-                meta: client.config().await.global.meta.clone(),
-                total_amount_msat: summary.total_amount(),
-                total_num_notes: summary.count_items(),
-                denominations_msat: summary,
-            };
-
-            info_map.insert(*federation_id, info);
+    /// Get the info for a single client in the multimint.
+    pub async fn info_for(&self, federation_id: &FederationId) -> Result<InfoResponse> {
+        if self.is_recovering(federation_id).await {
+            anyhow::bail!("Client for federation {federation_id} is still recovering");
         }
-
-        Ok(info_map)
+        let client = self
+            .get(federation_id)
+            .await
+            .ok_or_else(|| anyhow::anyhow!("No client found for federation: {federation_id}"))?;
+        info::client_info(&client).await
     }
 
     /// Update the gateway caches for all the lightning modules in the
-    /// multimint.
+    /// multimint, skipping federations without one.
     pub async fn update_gateway_caches(&self) -> Result<()> {
-        let clients = self.clients.lock().await;
+        let mut clients = Vec::new();
+        for client in self.all().await {
+            if client
+                .get_first_instance(&fedimint_ln_common::KIND)
+                .is_some()
+                && !self.is_recovering(&client.federation_id()).await
+            {
+                clients.push(client);
+            }
+        }
+
+        // Failures are logged and recorded per federation
+        join_all(
+            clients
+                .iter()
+                .map(|client| self.update_gateway_cache(client)),
+        )
+        .await;
 
-        for (federation_id, client) in clients.iter() {
-            warn!("Updating gateway cache for {:?}", federation_id);
-            let lightning_client = client.get_first_module::<LightningClientModule>();
-            if let Err(e) = lightning_client.update_gateway_cache().await {
+        Ok(())
+    }
+
+    /// Update the gateway cache of a client's lightning module, recording the
+    /// time of the refresh or the error in `gateway_cache_status`. Fails if
+    /// the federation has no lightning module.
+    pub async fn update_gateway_cache(&self, client: &ClientHandleArc) -> Result<()> {
+        let federation_id = client.federation_id();
+        if client
+            .get_first_instance(&fedimint_ln_common::KIND)
+            .is_none()
+        {
+            anyhow::bail!("Federation {federation_id} has no lightning module");
+        }
+        debug!("Updating gateway cache for {:?}", federation_id);
+        let res = client
+            .get_first_module::<LightningClientModule>()
+            .update_gateway_cache()
+            .await;
+        let now = fedimint_core::time::duration_since_epoch().as_secs();
+
+        let mut gateway_caches = self.gateway_caches.lock().await;
+        let status = gateway_caches.entry(federation_id).or_default();
+        status.last_attempt = Some(now);
+        match &res {
+            Ok(()) => {
+                status.last_refresh = Some(now);
+                status.error = None;
+            }
+            Err(e) => {
                 warn!(
                     "Failed to update gateway cache for {:?}: {:?}",
                     federation_id, e
                 );
+                status.error = Some(e.to_string());
             }
         }
+        res
+    }
 
-        Ok(())
+    /// Get the time of the last gateway cache refresh of each client.
+    pub async fn gateway_cache_status(&self) -> BTreeMap<FederationId, GatewayCacheStatus> {
+        self.gateway_caches.lock().await.clone()
+    }
+
+    /// Spawns a task updating the gateway caches of all the clients every
+    /// `interval`, so gateways registering or expiring after a client was
+    /// opened are picked up.
+    pub fn spawn_gateway_cache_refresh(&self, interval: Duration) {
+        let multimint = self.clone();
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            ticker.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+            // Clients update their cache when they are opened
+            ticker.tick().await;
+
+            loop {
+                ticker.tick().await;
+                if let Err(e) = multimint.update_gateway_caches().await {
+                    warn!("Failed to update gateway caches: {e:?}");
+                }
+            }
+        });
     }
 }
 
-async fn load_or_generate_mnemonic(db: &Database) -> Result<Mnemonic> {
-    Ok(
-        if let Ok(entropy) = Client::load_decodable_client_secret::<Vec<u8>>(db).await {
-            Mnemonic::from_entropy(&entropy)?
-        } else {
-            let mnemonic = if let Ok(words) = std::env::var("MULTIMINT_MNEMONIC_ENV") {
-                info!("Using provided mnemonic from environment variable");
-                Mnemonic::parse_in_normalized(bip39::Language::English, words.as_str())?
-            } else {
-                info!("Generating mnemonic and writing entropy to client storage");
-                Bip39RootSecretStrategy::<12>::random(&mut thread_rng())
-            };
+#[cfg(test)]
+mod tests {
+    use fedimint_core::util::SafeUrl;
+    use fedimint_core::PeerId;
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    /// Lookups used to wait on the client map's lock while another federation
+    /// was being opened. A federation whose guardian accepts connections but
+    /// never answers now only stalls its own background load.
+    #[tokio::test(flavor = "multi_thread", worker_threads = 4)]
+    async fn stalled_federation_does_not_block_lookups() -> Result<()> {
+        let multimint = MultiMint::builder()
+            .without_env_mnemonic()
+            .build_with_database(Database::new(MemDatabase::new(), Default::default()))
+            .await?;
 
-            Client::store_encodable_client_secret(&db, mnemonic.to_entropy()).await?;
-            mnemonic
-        },
-    )
+        // Accept connections and hold them open without ever answering
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let url = SafeUrl::parse(&format!("ws://{}/", listener.local_addr()?))?;
+        let guardian = tokio::spawn(async move {
+            let mut connections = Vec::new();
+            while let Ok((connection, _)) = listener.accept().await {
+                connections.push(connection);
+            }
+        });
+
+        let stalled = FederationId::dummy();
+        multimint
+            .load_client(FederationConfig {
+                invite_code: InviteCode::new(url, PeerId::from(0), stalled, None),
+            })
+            .await;
+        assert!(multimint.is_loading(&stalled).await);
+
+        let other = Some(FederationId::from_str(&"00".repeat(32))?);
+        tokio::time::timeout(
+            Duration::from_secs(1),
+            join_all((0..1_000).map(|_| async {
+                multimint.all().await;
+                multimint.ids().await;
+                multimint.get_or_default(other).await;
+                multimint.loading().await;
+            })),
+        )
+        .await?;
+
+        // The stalled federation is still being opened
+        assert!(multimint.is_loading(&stalled).await);
+        assert!(multimint.get(&stalled).await.is_none());
+
+        guardian.abort();
+        Ok(())
+    }
 }
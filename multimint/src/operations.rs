@@ -0,0 +1,24 @@
+//! Helpers for walking the operation log and reading the state of operations,
+//! shared by the multimint and its users.
+
+use std::time::Duration;
+
+use futures_util::stream::BoxStream;
+use futures_util::StreamExt;
+
+/// Number of operations fetched from the operation log at a time
+pub const PAGE_SIZE: usize = 100;
+
+/// How long to wait for a further state update before treating the last one
+/// received as the current state
+pub const UPDATE_TIMEOUT: Duration = Duration::from_millis(500);
+
+/// Drains the updates that are available right away and returns the last one.
+/// Subscribing also stores the outcome of operations that already finished.
+pub async fn latest_update<T>(mut updates: BoxStream<'_, T>) -> Option<T> {
+    let mut latest = None;
+    while let Ok(Some(update)) = tokio::time::timeout(UPDATE_TIMEOUT, updates.next()).await {
+        latest = Some(update);
+    }
+    latest
+}
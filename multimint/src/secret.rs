@@ -0,0 +1,272 @@
+//! Storage of the multimint's mnemonic, optionally encrypted with a passphrase.
+//!
+//! Without a passphrase the mnemonic's entropy is stored in plaintext as the
+//! client secret, like a standalone Fedimint client does. With one it is
+//! encrypted with ChaCha20-Poly1305 under a key derived from the passphrase
+//! with Argon2id, and only the encrypted secret is stored.
+
+use anyhow::{bail, Result};
+use argon2::Argon2;
+use bip39::Mnemonic;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{ChaCha20Poly1305, Key, Nonce};
+use fedimint_bip39::Bip39RootSecretStrategy;
+use fedimint_client::db::EncodedClientSecretKey;
+use fedimint_client::secret::RootSecretStrategy;
+use fedimint_client::Client;
+use fedimint_core::db::{Database, IDatabaseTransactionOpsCoreTyped};
+use rand::{thread_rng, RngCore};
+use tracing::info;
+use zeroize::Zeroizing;
+
+use crate::db::{EncryptedSecret, EncryptedSecretKey};
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+
+impl EncryptedSecret {
+    fn encrypt(entropy: &[u8], passphrase: &str) -> Result<Self> {
+        let mut salt = [0u8; SALT_LEN];
+        let mut nonce = [0u8; NONCE_LEN];
+        thread_rng().fill_bytes(&mut salt);
+        thread_rng().fill_bytes(&mut nonce);
+
+        let ciphertext = cipher(passphrase, &salt)?
+            .encrypt(Nonce::from_slice(&nonce), entropy)
+            .map_err(|_| anyhow::anyhow!("Failed to encrypt the mnemonic"))?;
+
+        Ok(Self {
+            salt,
+            nonce,
+            ciphertext,
+        })
+    }
+
+    fn decrypt(&self, passphrase: &str) -> Result<Vec<u8>> {
+        cipher(passphrase, &self.salt)?
+            .decrypt(Nonce::from_slice(&self.nonce), self.ciphertext.as_slice())
+            .map_err(|_| anyhow::anyhow!("Wrong passphrase, failed to decrypt the mnemonic"))
+    }
+}
+
+fn cipher(passphrase: &str, salt: &[u8]) -> Result<ChaCha20Poly1305> {
+    // Wiped when dropped, the cipher keeps its own copy
+    let mut key = Zeroizing::new([0u8; 32]);
+    Argon2::default()
+        .hash_password_into(passphrase.as_bytes(), salt, &mut key[..])
+        .map_err(|e| anyhow::anyhow!("Failed to derive the encryption key: {e}"))?;
+    Ok(ChaCha20Poly1305::new(Key::from_slice(&key[..])))
+}
+
+/// Check if the stored mnemonic is encrypted.
+pub async fn is_encrypted(db: &Database) -> bool {
+    db.begin_transaction_nc()
+        .await
+        .get_value(&EncryptedSecretKey)
+        .await
+        .is_some()
+}
+
+/// Load the stored mnemonic, decrypting it with `passphrase` if it's
+/// encrypted, or generate and store a new one.
+///
+/// A plaintext mnemonic is encrypted when a passphrase is given. New
+/// mnemonics are taken from the `MULTIMINT_MNEMONIC_ENV` environment variable
+/// if set and `mnemonic_from_env`, and generated randomly otherwise.
+pub async fn load_or_generate_mnemonic(
+    db: &Database,
+    passphrase: Option<&str>,
+    mnemonic_from_env: bool,
+) -> Result<Mnemonic> {
+    let env_words = std::env::var("MULTIMINT_MNEMONIC_ENV")
+        .ok()
+        .filter(|_| mnemonic_from_env);
+    load_or_generate_mnemonic_with(db, passphrase, env_words).await
+}
+
+/// `load_or_generate_mnemonic` with the words of the mnemonic from the
+/// environment, if any, already looked up.
+async fn load_or_generate_mnemonic_with(
+    db: &Database,
+    passphrase: Option<&str>,
+    env_words: Option<String>,
+) -> Result<Mnemonic> {
+    let encrypted = db
+        .begin_transaction_nc()
+        .await
+        .get_value(&EncryptedSecretKey)
+        .await;
+    if let Some(encrypted) = encrypted {
+        let Some(passphrase) = passphrase else {
+            bail!("The mnemonic is encrypted, a passphrase is required to unlock it");
+        };
+        return Ok(Mnemonic::from_entropy(&encrypted.decrypt(passphrase)?)?);
+    }
+
+    let mnemonic = if let Ok(entropy) = Client::load_decodable_client_secret::<Vec<u8>>(db).await {
+        Mnemonic::from_entropy(&entropy)?
+    } else {
+        let mnemonic = if let Some(words) = env_words {
+            info!("Using provided mnemonic from environment variable");
+            Mnemonic::parse_in_normalized(bip39::Language::English, words.as_str())?
+        } else {
+            info!("Generating mnemonic and writing entropy to client storage");
+            Bip39RootSecretStrategy::<12>::random(&mut thread_rng())
+        };
+
+        if passphrase.is_none() {
+            Client::store_encodable_client_secret(db, mnemonic.to_entropy()).await?;
+        }
+        mnemonic
+    };
+
+    if let Some(passphrase) = passphrase {
+        info!("Encrypting the stored mnemonic");
+        store_encrypted(db, &mnemonic, passphrase).await?;
+    }
+
+    Ok(mnemonic)
+}
+
+/// Change the passphrase the stored mnemonic is encrypted with. `current` is
+/// required if the mnemonic is encrypted, if `new` is `None` the mnemonic is
+/// decrypted and stored in plaintext again.
+pub async fn change_passphrase(
+    db: &Database,
+    current: Option<&str>,
+    new: Option<&str>,
+) -> Result<()> {
+    let encrypted = db
+        .begin_transaction_nc()
+        .await
+        .get_value(&EncryptedSecretKey)
+        .await;
+    let entropy = match (encrypted, current) {
+        (Some(encrypted), Some(current)) => encrypted.decrypt(current)?,
+        (Some(_), None) => bail!("The mnemonic is encrypted, the current passphrase is required"),
+        (None, _) => Client::load_decodable_client_secret::<Vec<u8>>(db)
+            .await
+            .map_err(|_| anyhow::anyhow!("No mnemonic is stored in the database"))?,
+    };
+    let mnemonic = Mnemonic::from_entropy(&entropy)?;
+
+    match new {
+        Some(new) => store_encrypted(db, &mnemonic, new).await,
+        None => {
+            let mut dbtx = db.begin_transaction().await;
+            dbtx.remove_entry(&EncryptedSecretKey).await;
+            dbtx.commit_tx_result()
+                .await
+                .map_err(|e| anyhow::anyhow!("Failed to remove the encrypted mnemonic: {e:?}"))?;
+            Client::store_encodable_client_secret(db, mnemonic.to_entropy()).await
+        }
+    }
+}
+
+/// Store the encrypted mnemonic and remove the plaintext one, if any, in the
+/// same transaction.
+///
+/// Removing the plaintext entry only deletes it logically: with RocksDB the
+/// old value can remain in existing SST and WAL files until they are
+/// compacted away, so a database that ever held the mnemonic in plaintext
+/// should be treated as exposed.
+async fn store_encrypted(db: &Database, mnemonic: &Mnemonic, passphrase: &str) -> Result<()> {
+    let encrypted = EncryptedSecret::encrypt(&mnemonic.to_entropy(), passphrase)?;
+    let mut dbtx = db.begin_transaction().await;
+    dbtx.insert_entry(&EncryptedSecretKey, &encrypted).await;
+    dbtx.remove_entry(&EncodedClientSecretKey).await;
+    dbtx.commit_tx_result()
+        .await
+        .map_err(|e| anyhow::anyhow!("Failed to store the encrypted mnemonic: {e:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use fedimint_core::db::mem_impl::MemDatabase;
+
+    use super::*;
+
+    fn in_memory_db() -> Database {
+        Database::new(MemDatabase::new(), Default::default())
+    }
+
+    #[tokio::test]
+    async fn encrypted_mnemonic_round_trips() -> Result<()> {
+        let db = in_memory_db();
+        let mnemonic = load_or_generate_mnemonic(&db, Some("passphrase"), false).await?;
+
+        assert!(is_encrypted(&db).await);
+        assert!(Client::load_decodable_client_secret::<Vec<u8>>(&db)
+            .await
+            .is_err());
+        assert_eq!(
+            load_or_generate_mnemonic(&db, Some("passphrase"), false).await?,
+            mnemonic
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn env_mnemonic_is_used_for_new_databases() -> Result<()> {
+        let words =
+            "ivory put armed include entire report oblige mystery ivory reunion siren actor";
+        // The environment isn't touched, other tests running concurrently read
+        // it
+        let db = in_memory_db();
+        let from_env = load_or_generate_mnemonic_with(&db, None, Some(words.to_owned())).await?;
+        let random = load_or_generate_mnemonic_with(&in_memory_db(), None, None).await?;
+
+        assert_eq!(from_env.to_string(), words);
+        assert_ne!(random.to_string(), words);
+        // The stored mnemonic takes precedence
+        assert_eq!(
+            load_or_generate_mnemonic_with(&db, None, Some(random.to_string())).await?,
+            from_env
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn wrong_or_missing_passphrase_is_rejected() -> Result<()> {
+        let db = in_memory_db();
+        load_or_generate_mnemonic(&db, Some("passphrase"), false).await?;
+
+        let err = load_or_generate_mnemonic(&db, Some("wrong"), false)
+            .await
+            .unwrap_err();
+        assert!(err.to_string().contains("Wrong passphrase"));
+        assert!(load_or_generate_mnemonic(&db, None, false).await.is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn change_passphrase_encrypts_rotates_and_removes() -> Result<()> {
+        let db = in_memory_db();
+        let mnemonic = load_or_generate_mnemonic(&db, None, false).await?;
+        assert!(!is_encrypted(&db).await);
+
+        change_passphrase(&db, None, Some("first")).await?;
+        assert!(is_encrypted(&db).await);
+        assert_eq!(
+            load_or_generate_mnemonic(&db, Some("first"), false).await?,
+            mnemonic
+        );
+
+        assert!(change_passphrase(&db, Some("wrong"), Some("second"))
+            .await
+            .is_err());
+        change_passphrase(&db, Some("first"), Some("second")).await?;
+        assert!(load_or_generate_mnemonic(&db, Some("first"), false)
+            .await
+            .is_err());
+        assert_eq!(
+            load_or_generate_mnemonic(&db, Some("second"), false).await?,
+            mnemonic
+        );
+
+        change_passphrase(&db, Some("second"), None).await?;
+        assert!(!is_encrypted(&db).await);
+        assert_eq!(load_or_generate_mnemonic(&db, None, false).await?, mnemonic);
+        Ok(())
+    }
+}
@@ -1,17 +1,100 @@
 use std::collections::BTreeMap;
 
 use fedimint_core::config::FederationId;
+use fedimint_core::core::ModuleInstanceId;
 use fedimint_core::{Amount, TieredCounts};
 use serde::Serialize;
 
-/// InfoResponse for getting the Federation Config info
+/// InfoResponse for getting the Federation Config info, serialized in
+/// camelCase like fedimint-clientd's `/v2/admin/info` response
 #[derive(Debug, Serialize)]
-#[serde(rename_all = "snake_case")]
+#[serde(rename_all = "camelCase")]
 pub struct InfoResponse {
     pub federation_id: FederationId,
+    /// `federation_name` from the federation's meta, if set
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub federation_name: Option<String>,
     pub network: String,
     pub meta: BTreeMap<String, String>,
+    /// Value of the ecash notes held, which is what can be spent right away
     pub total_amount_msat: Amount,
     pub total_num_notes: usize,
     pub denominations_msat: TieredCounts,
+    /// Same as `total_amount_msat`
+    pub spendable_msat: Amount,
+    pub pending: PendingBalances,
+    /// Spendable balance plus the pending amounts that are expected to arrive
+    /// or can still be reclaimed: incoming invoices, unconfirmed deposits and
+    /// in-flight spends
+    pub total_msat: Amount,
+}
+
+/// Amounts of operations that haven't completed yet
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingBalances {
+    /// Unexpired invoices that haven't been paid yet
+    pub incoming_invoices_msat: Amount,
+    /// Deposits seen onchain that don't have enough confirmations yet
+    pub unconfirmed_deposits_msat: Amount,
+    /// Notes spent out of band that the recipient hasn't reissued yet
+    pub in_flight_spends_msat: Amount,
+    /// Lightning payments and onchain withdrawals in progress, including fees
+    pub outgoing_payments_msat: Amount,
+}
+
+impl Default for PendingBalances {
+    fn default() -> Self {
+        Self {
+            incoming_invoices_msat: Amount::ZERO,
+            unconfirmed_deposits_msat: Amount::ZERO,
+            in_flight_spends_msat: Amount::ZERO,
+            outgoing_payments_msat: Amount::ZERO,
+        }
+    }
+}
+
+/// Progress of a client recovering its funds from the federation's history
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct RecoveryStatus {
+    /// All modules are recovered and the client was reopened
+    pub complete: bool,
+    /// Progress per module instance that needs recovery
+    pub modules: BTreeMap<ModuleInstanceId, ModuleRecoveryProgress>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ModuleRecoveryProgress {
+    pub complete: u32,
+    pub total: u32,
+}
+
+/// State of a client that is still being opened in the background
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct LoadStatus {
+    /// Number of failed attempts so far
+    pub attempts: u32,
+    /// Error of the last failed attempt
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    /// Unix timestamp of the next attempt after a failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_attempt: Option<u64>,
+}
+
+/// Refreshes of a client's lightning gateway cache, times are unix timestamps
+#[derive(Debug, Clone, Default, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub struct GatewayCacheStatus {
+    /// Last successful refresh
+    pub last_refresh: Option<u64>,
+    pub last_attempt: Option<u64>,
+    /// Error of the last attempt, if it failed
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
 }